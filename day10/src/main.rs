@@ -1,5 +1,8 @@
 #![allow(dead_code)]
-use std::{collections::HashMap, collections::HashSet, env, fs};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env, fs,
+};
 
 fn parse_space_separated_nums(line: &str) -> Vec<i64> {
     line.split(" ")
@@ -12,99 +15,222 @@ type Tile = (i64, i64);
 
 struct P {
     neighbors: HashMap<Tile, Vec<Tile>>,
+    start: Tile,
+    // The pipe hidden under S.
+    start_shape: char,
     maze: Vec<Vec<char>>,
     height: i64,
     width: i64,
 }
 
-fn parse(content: &str) -> P {
-    let maze: Vec<Vec<char>> = content
-        .split("\n")
-        .filter(|l| !l.is_empty())
-        .map(|l| l.chars().into_iter().collect())
-        .collect();
+const PIPES: &str = "|-LJ7F";
+
+// The two tiles a pipe at `tile` connects to, `None` if `symbol` isn't a pipe.
+fn pipe_ends(symbol: char, tile: Tile) -> Option<[Tile; 2]> {
+    let (i, j) = tile;
+    match symbol {
+        '|' => Some([(i - 1, j), (i + 1, j)]),
+        '-' => Some([(i, j - 1), (i, j + 1)]),
+        'L' => Some([(i - 1, j), (i, j + 1)]),
+        'J' => Some([(i - 1, j), (i, j - 1)]),
+        '7' => Some([(i + 1, j), (i, j - 1)]),
+        'F' => Some([(i + 1, j), (i, j + 1)]),
+        _ => None,
+    }
+}
 
-    let height = maze.len() as i64;
-    let width = maze[0].len() as i64;
+// A pipe end that doesn't lead to a pipe connecting back.
+#[derive(Debug, Clone, PartialEq)]
+struct DanglingPipe {
+    tile: Tile,
+    symbol: char,
+    towards: Tile,
+}
 
-    let mut spos: Option<Tile> = None;
-    for i in 0..height {
-        for j in 0..width {
-            if maze[i as usize][j as usize] == 'S' {
-                spos = Some((i, j));
-                break;
+#[derive(Debug)]
+enum MazeError {
+    NoStart,
+    // No pipe under S closes a loop; lists the dangling pipes found for every
+    // shape S could have.
+    NoLoop(Vec<DanglingPipe>),
+    // Several pipes under S would close a loop.
+    AmbiguousStart(Vec<char>),
+}
+
+impl std::fmt::Display for MazeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MazeError::NoStart => write!(f, "there is no S in the maze"),
+            MazeError::NoLoop(dangling) => {
+                write!(f, "no closed loop goes through S")?;
+                for d in dangling {
+                    write!(
+                        f,
+                        "\n  '{}' at {:?} points to {:?}, which doesn't connect back",
+                        d.symbol, d.tile, d.towards
+                    )?;
+                }
+                Ok(())
+            }
+            MazeError::AmbiguousStart(shapes) => {
+                write!(f, "S could be any of {shapes:?}")
             }
         }
-        if spos.is_some() {
-            break;
+    }
+}
+
+fn parse_maze(content: &str) -> Vec<Vec<char>> {
+    content
+        .split("\n")
+        .filter(|l| !l.is_empty())
+        .map(|l| l.chars().collect())
+        .collect()
+}
+
+fn find_start(maze: &[Vec<char>]) -> Result<Tile, MazeError> {
+    for (i, row) in maze.iter().enumerate() {
+        if let Some(j) = row.iter().position(|c| *c == 'S') {
+            return Ok((i as i64, j as i64));
         }
     }
-    let spos = spos.unwrap();
-    let mut q: Vec<Tile> = vec![spos.clone()];
-    let mut neighbors: HashMap<Tile, Vec<Tile>> = HashMap::new();
-    let m = |i: i64, j: i64| -> char { maze[i as usize][j as usize] };
-
-    while !q.is_empty() {
-        let cur = q.pop().unwrap();
-        let cur_symbol = maze[cur.0 as usize][cur.1 as usize];
-
-        let ns = neighbors.entry(cur).or_insert(vec![]);
-        let mut push = |i, j| {
-            let n = (i, j);
-            if !ns.contains(&n) {
-                // println!("{:?} -> {:?}", cur, n);
-                q.push(n);
-                ns.push(n);
-            }
-        };
-        if cur_symbol == 'S' {
-            if cur.0 >= 1 && "|7F".contains(m(cur.0 - 1, cur.1)) {
-                push(cur.0 - 1, cur.1);
-            }
+    Err(MazeError::NoStart)
+}
 
-            if cur.0 + 1 < height && "|LJ".contains(m(cur.0 + 1, cur.1)) {
-                push(cur.0 + 1, cur.1);
-            }
+// The pipes reachable from S, whether or not they make a loop.
+struct PipeGraph {
+    start: Tile,
+    // Only the connections that go both ways. S connects to every pipe that
+    // points at it, since its shape isn't known.
+    neighbors: HashMap<Tile, Vec<Tile>>,
+}
 
-            if cur.1 >= 1 && "-FL".contains(m(cur.0, cur.1 - 1)) {
-                push(cur.0, cur.1 - 1);
-            }
+fn reachable_pipes(maze: &[Vec<char>]) -> Result<PipeGraph, MazeError> {
+    let start = find_start(maze)?;
+    let ends_at = |tile: Tile| -> Option<[Tile; 2]> {
+        let symbol = *maze.get(tile.0 as usize)?.get(tile.1 as usize)?;
+        pipe_ends(symbol, tile)
+    };
+
+    let start_neighbors: Vec<Tile> = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .map(|(di, dj)| (start.0 + di, start.1 + dj))
+        .filter(|n| ends_at(*n).is_some_and(|ends| ends.contains(&start)))
+        .collect();
+    let mut neighbors = HashMap::from([(start, start_neighbors.clone())]);
+    let mut q = start_neighbors;
+    while let Some(cur) = q.pop() {
+        if neighbors.contains_key(&cur) {
+            continue;
+        }
+        let connected: Vec<Tile> = ends_at(cur)
+            .unwrap()
+            .into_iter()
+            .filter(|n| *n == start || ends_at(*n).is_some_and(|ends| ends.contains(&cur)))
+            .collect();
+        q.extend(&connected);
+        neighbors.insert(cur, connected);
+    }
+    Ok(PipeGraph { start, neighbors })
+}
+
+fn parse(content: &str) -> Result<P, MazeError> {
+    let maze = parse_maze(content);
 
-            if cur.1 + 1 < height && "-7J".contains(m(cur.0, cur.1 + 1)) {
-                push(cur.0, cur.1 + 1);
+    let height = maze.len() as i64;
+    let width = maze.first().map_or(0, |row| row.len() as i64);
+
+    let graph = reachable_pipes(&maze)?;
+    let spos = graph.start;
+
+    // Walks the pipe graph from S as if S were `start_shape`, checking that
+    // every pipe on the way connects to two pipes that connect back to it.
+    let explore = |start_shape: char| -> Result<HashMap<Tile, Vec<Tile>>, Vec<DanglingPipe>> {
+        let start_ends = pipe_ends(start_shape, spos).unwrap();
+        let symbol_at = |tile: Tile| -> char {
+            if tile == spos {
+                start_shape
+            } else {
+                maze[tile.0 as usize][tile.1 as usize]
             }
-        } else {
-            let next_ns: Vec<Tile> = match cur_symbol {
-                '|' => vec![(cur.0 - 1, cur.1), (cur.0 + 1, cur.1)],
-                '-' => vec![(cur.0, cur.1 - 1), (cur.0, cur.1 + 1)],
-                'L' => vec![(cur.0 - 1, cur.1), (cur.0, cur.1 + 1)],
-                'J' => vec![(cur.0 - 1, cur.1), (cur.0, cur.1 - 1)],
-                '7' => vec![(cur.0 + 1, cur.1), (cur.0, cur.1 - 1)],
-                'F' => vec![(cur.0 + 1, cur.1), (cur.0, cur.1 + 1)],
-
-                _ => panic!("unexpected symbol"),
-            };
-            for n in next_ns {
-                push(n.0, n.1);
+        };
+        // S only keeps the connections its shape points to.
+        let connects_back = |from: Tile, to: Tile| -> bool {
+            graph.neighbors[&from].contains(&to)
+                && (from != spos || start_ends.contains(&to))
+                && (to != spos || start_ends.contains(&from))
+        };
+        let mut q: Vec<Tile> = vec![spos];
+        let mut neighbors: HashMap<Tile, Vec<Tile>> = HashMap::new();
+        let mut dangling = Vec::<DanglingPipe>::new();
+        while let Some(cur) = q.pop() {
+            if neighbors.contains_key(&cur) {
+                continue;
+            }
+            let cur_symbol = symbol_at(cur);
+            let ends = pipe_ends(cur_symbol, cur).unwrap();
+            for n in ends {
+                if connects_back(cur, n) {
+                    q.push(n);
+                } else {
+                    dangling.push(DanglingPipe {
+                        tile: cur,
+                        symbol: cur_symbol,
+                        towards: n,
+                    });
+                }
             }
+            neighbors.insert(cur, ends.to_vec());
+        }
+        if dangling.is_empty() {
+            Ok(neighbors)
+        } else {
+            Err(dangling)
+        }
+    };
+
+    let mut loops = Vec::<(char, HashMap<Tile, Vec<Tile>>)>::new();
+    let mut dangling = Vec::<DanglingPipe>::new();
+    for start_shape in PIPES.chars() {
+        match explore(start_shape) {
+            Ok(neighbors) => loops.push((start_shape, neighbors)),
+            Err(d) => dangling.extend(d.into_iter().filter(|d| d.tile != spos)),
         }
     }
-    P {
-        neighbors,
-        maze,
-        height,
-        width,
+    // Each pipe connects to exactly two others, so whatever was reached is a
+    // single closed loop.
+    match loops.len() {
+        0 => {
+            dangling.sort_by_key(|d| (d.tile, d.towards));
+            dangling.dedup();
+            Err(MazeError::NoLoop(dangling))
+        }
+        1 => {
+            let (start_shape, neighbors) = loops.pop().unwrap();
+            Ok(P {
+                neighbors,
+                start: spos,
+                start_shape,
+                maze,
+                height,
+                width,
+            })
+        }
+        _ => Err(MazeError::AmbiguousStart(
+            loops.iter().map(|(shape, _)| *shape).collect(),
+        )),
     }
 }
-fn part_one(content: &str) -> i64 {
-    let p = parse(content);
-    assert_eq!(p.neighbors.len() % 2, 0);
 
-    (p.neighbors.len() as i64) / 2
+// Also answers for mazes where the pipes from S don't close up.
+fn part_one(content: &str) -> i64 {
+    let graph = reachable_pipes(&parse_maze(content)).unwrap();
+    distance_map(graph.start, &graph.neighbors).max_distance as i64
 }
 
-fn part_two(content: &str) -> i64 {
-    let p = parse(content);
+// Counts the enclosed tiles with a flood fill from the border that can also
+// squeeze between adjacent pipes.
+fn part_two_flood_fill(content: &str) -> i64 {
+    let p = parse(content).unwrap();
 
     let is_part_of_loop = |i: i64, j: i64| -> bool { p.neighbors.contains_key(&(i, j)) };
 
@@ -118,26 +244,25 @@ fn part_two(content: &str) -> i64 {
         n.into_iter().filter(tile_is_ok).collect()
     };
 
-    // CBP == "coordinates between pipes"
-    type CBP = (i64, i64, i64, i64);
-    let mut reachable_cbp: HashSet<CBP> = HashSet::new();
+    // Cbp == "coordinates between pipes"
+    type Cbp = (i64, i64, i64, i64);
+    let mut reachable_cbp: HashSet<Cbp> = HashSet::new();
 
-    let cbp_is_squeezable = |c: &CBP| -> bool {
-        return !p
-            .neighbors
+    let cbp_is_squeezable = |c: &Cbp| -> bool {
+        !p.neighbors
             .get(&(c.0, c.1))
             .is_some_and(|x| x.contains(&(c.2, c.3)))
             && !p
                 .neighbors
                 .get(&(c.2, c.3))
-                .is_some_and(|x| x.contains(&(c.0, c.1)));
+                .is_some_and(|x| x.contains(&(c.0, c.1)))
     };
 
     let cbp_is_ok =
-        |cbp: &CBP| -> bool { tile_is_ok(&(cbp.0, cbp.1)) && tile_is_ok(&(cbp.2, cbp.3)) };
+        |cbp: &Cbp| -> bool { tile_is_ok(&(cbp.0, cbp.1)) && tile_is_ok(&(cbp.2, cbp.3)) };
 
-    let cbp_neighbors_of_tile = |i: i64, j: i64| -> Vec<CBP> {
-        let n: Vec<CBP> = vec![
+    let cbp_neighbors_of_tile = |i: i64, j: i64| -> Vec<Cbp> {
+        let n: Vec<Cbp> = vec![
             (i - 1, j - 1, i - 1, j),
             (i - 1, j - 1, i, j - 1),
             (i - 1, j, i - 1, j + 1),
@@ -154,7 +279,7 @@ fn part_two(content: &str) -> i64 {
             .collect()
     };
 
-    let cbp_neighbors_of_cbp = |c: CBP| -> Vec<CBP> {
+    let cbp_neighbors_of_cbp = |c: Cbp| -> Vec<Cbp> {
         let n = if c.0 == c.2 {
             vec![
                 // Squeeze north.
@@ -194,7 +319,7 @@ fn part_two(content: &str) -> i64 {
             .collect()
     };
 
-    let tile_neighbors_of_cbp = |c: CBP| -> Vec<Tile> {
+    let tile_neighbors_of_cbp = |c: Cbp| -> Vec<Tile> {
         let n = if c.0 == c.2 {
             vec![
                 (c.0 - 1, c.1),
@@ -227,11 +352,10 @@ fn part_two(content: &str) -> i64 {
             return;
         }
         let mut q: Vec<Tile> = vec![(si, sj)];
-        let mut cbp_q: Vec<CBP> = vec![];
+        let mut cbp_q: Vec<Cbp> = vec![];
 
         while !q.is_empty() || !cbp_q.is_empty() {
-            while !q.is_empty() {
-                let (i, j) = q.pop().unwrap();
+            while let Some((i, j)) = q.pop() {
                 if can_escape[i as usize][j as usize] {
                     continue;
                 }
@@ -250,8 +374,7 @@ fn part_two(content: &str) -> i64 {
                 });
             }
 
-            while !cbp_q.is_empty() {
-                let cur_cbp = cbp_q.pop().unwrap();
+            while let Some(cur_cbp) = cbp_q.pop() {
                 if !reachable_cbp.insert(cur_cbp) {
                     continue;
                 }
@@ -276,19 +399,6 @@ fn part_two(content: &str) -> i64 {
         traverse(0, j);
         traverse(p.height - 1, j);
     }
-    // for i in 0..p.height {
-    //     for j in 0..p.width {
-    //         if is_part_of_loop(i, j) {
-    //             print!("{}", p.maze[i][j]);
-    //         } else if !can_escape[i][j] {
-    //             print!("I");
-    //         } else {
-    //             print!("O");
-    //         }
-    //     }
-    //     println!("");
-    // }
-
     let mut count_not_escapeable = 0;
     for i in 0..p.height {
         for j in 0..p.width {
@@ -300,13 +410,365 @@ fn part_two(content: &str) -> i64 {
     count_not_escapeable
 }
 
+// The loop's tiles in walking order, starting at S. If the pipes don't close
+// up, the path instead runs from the end of the longest branch through S to
+// the end of the second longest one.
+fn loop_path(start: Tile, neighbors: &HashMap<Tile, Vec<Tile>>) -> Vec<Tile> {
+    // Follows the pipes away from S through `first` until getting back to S or
+    // reaching a pipe that doesn't continue. Also tells whether it got back,
+    // which can only happen through another neighbour of S.
+    let walk = |first: Tile| -> (Vec<Tile>, bool) {
+        let mut path = Vec::<Tile>::new();
+        let mut prev = start;
+        let mut cur = first;
+        while cur != start && neighbors.contains_key(&cur) {
+            path.push(cur);
+            let Some(next) = neighbors[&cur].iter().find(|n| **n != prev) else {
+                break;
+            };
+            prev = cur;
+            cur = *next;
+        }
+        (path, cur == start)
+    };
+    let mut branches = Vec::<Vec<Tile>>::new();
+    for first in &neighbors[&start] {
+        let (branch, closed) = walk(*first);
+        if closed {
+            return [vec![start], branch].concat();
+        }
+        branches.push(branch);
+    }
+    // the pipes don't close up, so the path runs from one open end to the other
+    branches.sort_by_key(|branch| std::cmp::Reverse(branch.len()));
+    let mut branches = branches.into_iter();
+    let mut path = branches.next().unwrap_or_default();
+    path.reverse();
+    path.push(start);
+    path.extend(branches.next().unwrap_or_default());
+    path
+}
+
+// How far every pipe reachable from S is when walking along the pipes.
+#[derive(Debug)]
+struct DistanceMap {
+    distances: HashMap<Tile, usize>,
+    farthest: Vec<Tile>,
+    max_distance: usize,
+    // The pipes in the order they are walked, starting at S for a closed loop.
+    path: Vec<Tile>,
+}
+
+fn distance_map(start: Tile, neighbors: &HashMap<Tile, Vec<Tile>>) -> DistanceMap {
+    let mut distances: HashMap<Tile, usize> = HashMap::from([(start, 0)]);
+    let mut q = VecDeque::from([start]);
+    while let Some(cur) = q.pop_front() {
+        for n in &neighbors[&cur] {
+            if neighbors.contains_key(n) && !distances.contains_key(n) {
+                distances.insert(*n, distances[&cur] + 1);
+                q.push_back(*n);
+            }
+        }
+    }
+    let max_distance = *distances.values().max().unwrap();
+    let mut farthest: Vec<Tile> = distances
+        .iter()
+        .filter(|(_, distance)| **distance == max_distance)
+        .map(|(tile, _)| *tile)
+        .collect();
+    farthest.sort();
+    DistanceMap {
+        distances,
+        farthest,
+        max_distance,
+        path: loop_path(start, neighbors),
+    }
+}
+
+// Every pipe with its distance from S, in path order.
+fn distance_report(graph: &PipeGraph) -> String {
+    let map = distance_map(graph.start, &graph.neighbors);
+    let mut out = format!(
+        "farthest at {:?}, {} steps from S\n",
+        map.farthest, map.max_distance
+    );
+    for tile in &map.path {
+        out += &format!("{tile:?}: {}\n", map.distances[tile]);
+    }
+    out
+}
+
+// The corners of the loop, in walking order.
+fn loop_vertices(path: &[Tile]) -> Vec<Tile> {
+    (0..path.len())
+        .filter(|i| {
+            let prev = path[(i + path.len() - 1) % path.len()];
+            let next = path[(i + 1) % path.len()];
+            // a tile is a corner unless its neighbours on the path line up
+            prev.0 != next.0 && prev.1 != next.1
+        })
+        .map(|i| path[i])
+        .collect()
+}
+
+// Pick's theorem: `area = interior + boundary / 2 - 1`, with the area from the
+// shoelace formula over the corners and every loop tile on the boundary.
+fn count_enclosed(p: &P) -> i64 {
+    let path = loop_path(p.start, &p.neighbors);
+    let vertices = loop_vertices(&path);
+    let twice_area: i64 = (0..vertices.len())
+        .map(|i| {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<i64>()
+        .abs();
+    (twice_area - path.len() as i64 + 2) / 2
+}
+
+fn part_two(content: &str) -> i64 {
+    count_enclosed(&parse(content).unwrap())
+}
+
+// The enclosed tiles, found by scanning every row and flipping between
+// outside and inside whenever a loop pipe reaching north is crossed.
+fn enclosed_tiles(p: &P) -> HashSet<Tile> {
+    let mut enclosed = HashSet::<Tile>::new();
+    for i in 0..p.height {
+        let mut inside = false;
+        for j in 0..p.width {
+            if p.neighbors.contains_key(&(i, j)) {
+                if p.neighbors[&(i, j)].contains(&(i - 1, j)) {
+                    inside = !inside;
+                }
+            } else if inside {
+                enclosed.insert((i, j));
+            }
+        }
+    }
+    enclosed
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RenderFormat {
+    Ansi,
+    Svg,
+}
+
+fn box_drawing(symbol: char) -> char {
+    match symbol {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        _ => ' ',
+    }
+}
+
+// The maze with the loop in box-drawing characters, other pipes dimmed, the
+// enclosed tiles shaded and the farthest tiles from S marked.
+fn render(p: &P, format: RenderFormat) -> String {
+    let enclosed = enclosed_tiles(p);
+    let DistanceMap {
+        farthest,
+        max_distance,
+        ..
+    } = distance_map(p.start, &p.neighbors);
+    let symbol_at = |tile: Tile| -> char {
+        if tile == p.start {
+            p.start_shape
+        } else {
+            p.maze[tile.0 as usize][tile.1 as usize]
+        }
+    };
+
+    let mut out = String::new();
+    match format {
+        RenderFormat::Ansi => {
+            for i in 0..p.height {
+                for j in 0..p.width {
+                    let tile = (i, j);
+                    let glyph = box_drawing(symbol_at(tile));
+                    let style = if tile == p.start {
+                        "\x1b[1;32m"
+                    } else if farthest.contains(&tile) {
+                        "\x1b[1;31m"
+                    } else if p.neighbors.contains_key(&tile) {
+                        "\x1b[1m"
+                    } else if enclosed.contains(&tile) {
+                        "\x1b[2;43m"
+                    } else {
+                        "\x1b[2m"
+                    };
+                    out += &format!("{style}{glyph}\x1b[0m");
+                }
+                out.push('\n');
+            }
+            out += &format!(
+                "\x1b[1;32mS\x1b[0m is at {:?}, \x1b[1;31mfarthest\x1b[0m at {:?} ({max_distance} steps), {} tiles enclosed\n",
+                p.start,
+                farthest,
+                enclosed.len()
+            );
+        }
+        RenderFormat::Svg => {
+            const CELL: i64 = 10;
+            let center = |tile: Tile| (tile.1 * CELL + CELL / 2, tile.0 * CELL + CELL / 2);
+            out += &format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+                p.width * CELL,
+                p.height * CELL
+            );
+            for tile in &enclosed {
+                out += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"khaki\"/>\n",
+                    tile.1 * CELL,
+                    tile.0 * CELL
+                );
+            }
+            for i in 0..p.height {
+                for j in 0..p.width {
+                    let tile = (i, j);
+                    let Some(ends) = pipe_ends(symbol_at(tile), tile) else {
+                        continue;
+                    };
+                    let (cx, cy) = center(tile);
+                    // each pipe is drawn from the middle of one edge to the other
+                    let [a, b] = ends.map(|end| {
+                        let (ex, ey) = center(end);
+                        ((cx + ex) / 2, (cy + ey) / 2)
+                    });
+                    let style = if p.neighbors.contains_key(&tile) {
+                        "stroke=\"black\" stroke-width=\"2\""
+                    } else {
+                        "stroke=\"lightgray\" stroke-width=\"1\""
+                    };
+                    out += &format!(
+                        "<polyline points=\"{},{} {cx},{cy} {},{}\" fill=\"none\" {style}/>\n",
+                        a.0, a.1, b.0, b.1
+                    );
+                }
+            }
+            let (sx, sy) = center(p.start);
+            out += &format!("<circle cx=\"{sx}\" cy=\"{sy}\" r=\"4\" fill=\"green\"/>\n");
+            for tile in &farthest {
+                let (fx, fy) = center(*tile);
+                out += &format!("<circle cx=\"{fx}\" cy=\"{fy}\" r=\"4\" fill=\"red\"/>\n");
+                out += &format!(
+                    "<text x=\"{}\" y=\"{fy}\" font-size=\"{CELL}\" fill=\"red\">{max_distance}</text>\n",
+                    fx + CELL / 2
+                );
+            }
+            out += "</svg>\n";
+        }
+    }
+    out
+}
+
 fn main() {
     let file_path = env::args().nth(1).unwrap();
     let content = fs::read_to_string(file_path).unwrap();
 
+    let graph = match reachable_pipes(&parse_maze(&content)) {
+        Ok(graph) => graph,
+        Err(err) => {
+            println!("the maze is invalid: {err}");
+            return;
+        }
+    };
+    let maze = parse(&content);
+    match &maze {
+        Ok(p) => println!("S is a '{}' pipe", p.start_shape),
+        Err(err) => println!("the maze is invalid: {err}"),
+    }
+
     let answer1 = part_one(&content);
     println!("the answer for the first part is: {answer1}");
 
-    let answer2 = part_two(&content);
-    println!("the answer for the second part is: {answer2}");
+    if let Ok(p) = &maze {
+        let answer2 = part_two(&content);
+        println!("the answer for the second part is: {answer2}");
+
+        // `render ansi` or `render svg` redraws the maze.
+        if env::args().nth(2).as_deref() == Some("render") {
+            let format = match env::args().nth(3).as_deref() {
+                Some("svg") => RenderFormat::Svg,
+                _ => RenderFormat::Ansi,
+            };
+            print!("{}", render(p, format));
+        }
+    }
+    // `distances` works on the pipes from S even when they don't make a loop.
+    if env::args().nth(2).as_deref() == Some("distances") {
+        print!("{}", distance_report(&graph));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_matches_flood_fill() {
+        for i in 1..=6 {
+            let path = format!("{}/example{i}.txt", env!("CARGO_MANIFEST_DIR"));
+            let content = fs::read_to_string(path).unwrap();
+            assert_eq!(
+                part_two(&content),
+                part_two_flood_fill(&content),
+                "example{i}"
+            );
+        }
+    }
+
+    #[test]
+    fn pick_matches_flood_fill_on_non_square_grids() {
+        let wide =
+            "..........\n.S------7.\n.|F----7|.\n.||....||.\n.|L-7F-J|.\n.L--JL--J.\n..........\n";
+        let tall = ".....\n.S-7.\n.|.|.\n.|.|.\n.|.|.\n.|.|.\n.|.|.\n.L-J.\n.....\n";
+        for content in [wide, tall] {
+            assert_eq!(part_two(content), part_two_flood_fill(content), "{content}");
+        }
+        assert_eq!(part_two(wide), 0);
+        assert_eq!(part_two(tall), 5);
+    }
+
+    #[test]
+    fn distances_on_open_pipes() {
+        let content = "L-S-7\n....|\n....J\n";
+        assert!(parse(content).is_err());
+        let graph = reachable_pipes(&parse_maze(content)).unwrap();
+        let map = distance_map(graph.start, &graph.neighbors);
+        assert_eq!(map.farthest, vec![(2, 4)]);
+        assert_eq!(map.max_distance, 4);
+        assert_eq!(
+            map.path,
+            vec![(2, 4), (1, 4), (0, 4), (0, 3), (0, 2), (0, 1), (0, 0)]
+        );
+        assert_eq!(part_one(content), 4);
+    }
+
+    #[test]
+    fn distances_past_a_dead_end_at_s() {
+        let content = ".|...\n.S-7.\n.|.|.\n.L-J.\n";
+        assert_eq!(parse(content).unwrap().start_shape, 'F');
+        let graph = reachable_pipes(&parse_maze(content)).unwrap();
+        let map = distance_map(graph.start, &graph.neighbors);
+        assert_eq!(map.path.len(), 8);
+        assert_eq!(map.path[0], graph.start);
+        assert_eq!((map.farthest, map.max_distance), (vec![(3, 3)], 4));
+    }
+
+    #[test]
+    fn distances_on_closed_loops() {
+        let content =
+            fs::read_to_string(format!("{}/example2.txt", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        let p = parse(&content).unwrap();
+        let map = distance_map(p.start, &p.neighbors);
+        assert_eq!(map.path.len(), 16);
+        assert_eq!(map.path[0], p.start);
+        assert_eq!((map.farthest.clone(), map.max_distance), (vec![(2, 4)], 8));
+    }
 }
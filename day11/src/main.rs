@@ -1,52 +1,395 @@
 #![allow(dead_code)]
-use std::{env, fs};
+use std::{collections::BinaryHeap, env, fs};
 
-fn run_with_multiplier(content: &str, empty_space_multiplier: i64) -> u64 {
-    let grid: Vec<Vec<char>> = content
+// How many rows (or columns) every empty row (or column) becomes.
+#[derive(Debug, Clone, Copy)]
+struct Expansion {
+    rows: i64,
+    cols: i64,
+}
+
+fn parse_grid(content: &str) -> Vec<Vec<char>> {
+    content
         .split("\n")
         .filter(|line| !line.is_empty())
         .map(|line| line.chars().collect())
-        .collect();
+        .collect()
+}
 
+// The `(row, col)` of every galaxy once empty space has expanded.
+fn expanded_galaxies(grid: &[Vec<char>], expansion: Expansion) -> Vec<(i64, i64)> {
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
     let row_empty: Vec<bool> = grid.iter().map(|row| !row.contains(&'#')).collect();
-    let col_empty: Vec<bool> = (0..grid.len())
-        .into_iter()
-        .map(|col_index| (0..grid[0].len()).all(|row_index| grid[row_index][col_index] != '#'))
+    let col_empty: Vec<bool> = (0..width)
+        .map(|col_index| grid.iter().all(|row| row.get(col_index) != Some(&'#')))
         .collect();
 
     let mut galaxies: Vec<(i64, i64)> = vec![];
     let mut num_empty_rows = 0;
-    for i in 0..grid.len() {
+    for (i, row) in grid.iter().enumerate() {
         num_empty_rows += row_empty[i] as i64;
         let mut num_empty_cols = 0;
-        for j in 0..grid[0].len() {
+        for (j, c) in row.iter().enumerate() {
             num_empty_cols += col_empty[j] as i64;
-            if grid[i][j] == '#' {
+            if *c == '#' {
                 galaxies.push((
-                    i as i64 + num_empty_rows * (empty_space_multiplier - 1),
-                    j as i64 + num_empty_cols * (empty_space_multiplier - 1),
+                    i as i64 + num_empty_rows * (expansion.rows - 1),
+                    j as i64 + num_empty_cols * (expansion.cols - 1),
                 ));
             }
         }
     }
+    galaxies
+}
+
+// The sum of `|a - b|` over all pairs. Once sorted, the k-th value is the
+// larger one in k pairs, so it adds `k * value` minus the sum of the values
+// before it.
+fn sum_pairwise_differences(mut values: Vec<i64>) -> u128 {
+    values.sort_unstable();
+    let mut prefix_sum: i128 = 0;
+    let mut sum: i128 = 0;
+    for (k, value) in values.into_iter().enumerate() {
+        sum += k as i128 * value as i128 - prefix_sum;
+        prefix_sum += value as i128;
+    }
+    sum as u128
+}
+
+// Manhattan distances split into one sum per axis, O(n log n).
+fn sum_distances(galaxies: &[(i64, i64)]) -> u128 {
+    sum_pairwise_differences(galaxies.iter().map(|g| g.0).collect())
+        + sum_pairwise_differences(galaxies.iter().map(|g| g.1).collect())
+}
+
+fn run_with_expansion(content: &str, expansion: Expansion) -> u128 {
+    sum_distances(&expanded_galaxies(&parse_grid(content), expansion))
+}
+
+fn run_with_multiplier(content: &str, empty_space_multiplier: i64) -> u128 {
+    run_with_expansion(
+        content,
+        Expansion {
+            rows: empty_space_multiplier,
+            cols: empty_space_multiplier,
+        },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    fn from_arg(arg: &str) -> Option<Metric> {
+        match arg {
+            "manhattan" => Some(Metric::Manhattan),
+            "chebyshev" => Some(Metric::Chebyshev),
+            "euclidean" => Some(Metric::Euclidean),
+            _ => None,
+        }
+    }
+
+    // A key that orders pairs the same way as their distance does; for
+    // Euclidean distances it is the squared distance, to stay in integers.
+    fn key(&self, a: (i64, i64), b: (i64, i64)) -> u128 {
+        self.key_of_gaps(a.0.abs_diff(b.0), a.1.abs_diff(b.1))
+    }
+
+    fn key_of_gaps(&self, dx: u64, dy: u64) -> u128 {
+        let (dx, dy) = (dx as u128, dy as u128);
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => dx * dx + dy * dy,
+        }
+    }
+
+    // The smallest key of two points that are `d` apart along one axis.
+    fn axis_key(&self, d: u64) -> u128 {
+        match self {
+            Metric::Euclidean => d as u128 * d as u128,
+            _ => d as u128,
+        }
+    }
+
+    fn distance(&self, key: u128) -> f64 {
+        match self {
+            Metric::Euclidean => (key as f64).sqrt(),
+            _ => key as f64,
+        }
+    }
+
+    // The largest key of two points less than `distance` apart, if any.
+    fn max_key_below(&self, distance: f64) -> Option<u128> {
+        let bound = match self {
+            Metric::Euclidean => distance * distance,
+            _ => distance,
+        };
+        (bound.ceil() as u128).checked_sub(1)
+    }
+}
+
+// The smallest box holding some galaxies, as `(min_row, max_row, min_col, max_col)`.
+type BoundingBox = (i64, i64, i64, i64);
+
+// How far `x` is from `low..=high` at the least and at the most.
+fn gaps(x: i64, low: i64, high: i64) -> (u64, u64) {
+    let nearest = if x < low {
+        low.abs_diff(x)
+    } else {
+        x.abs_diff(high.min(x))
+    };
+    (nearest, x.abs_diff(low).max(x.abs_diff(high)))
+}
+
+// A 2-d tree over the expanded galaxies: `ids` is ordered so that the middle
+// of every slice splits the rest by row (at even depths) or by column.
+#[derive(Debug)]
+struct GalaxyIndex {
+    galaxies: Vec<(i64, i64)>,
+    ids: Vec<usize>,
+    // `boxes[i]` bounds the slice of `ids` whose middle is at `i`.
+    boxes: Vec<BoundingBox>,
+}
+
+fn coordinate(galaxy: (i64, i64), depth: usize) -> i64 {
+    if depth.is_multiple_of(2) {
+        galaxy.0
+    } else {
+        galaxy.1
+    }
+}
+
+impl GalaxyIndex {
+    fn new(galaxies: Vec<(i64, i64)>) -> GalaxyIndex {
+        fn build(
+            galaxies: &[(i64, i64)],
+            ids: &mut [usize],
+            boxes: &mut [BoundingBox],
+            depth: usize,
+        ) {
+            if ids.is_empty() {
+                return;
+            }
+            let mid = ids.len() / 2;
+            boxes[mid] = ids
+                .iter()
+                .map(|id| galaxies[*id])
+                .fold((i64::MAX, i64::MIN, i64::MAX, i64::MIN), |b, g| {
+                    (b.0.min(g.0), b.1.max(g.0), b.2.min(g.1), b.3.max(g.1))
+                });
+            ids.select_nth_unstable_by_key(mid, |id| coordinate(galaxies[*id], depth));
+            let (left, right) = ids.split_at_mut(mid);
+            let (left_boxes, right_boxes) = boxes.split_at_mut(mid);
+            build(galaxies, left, left_boxes, depth + 1);
+            build(galaxies, &mut right[1..], &mut right_boxes[1..], depth + 1);
+        }
+        let mut ids: Vec<usize> = (0..galaxies.len()).collect();
+        let mut boxes = vec![(0, 0, 0, 0); galaxies.len()];
+        build(&galaxies, &mut ids, &mut boxes, 0);
+        GalaxyIndex {
+            galaxies,
+            ids,
+            boxes,
+        }
+    }
+
+    // The `k` galaxies closest to `galaxy` (itself excluded), closest first,
+    // as `(key, id)`.
+    fn k_nearest(&self, galaxy: usize, k: usize, metric: Metric) -> Vec<(u128, usize)> {
+        fn search(
+            index: &GalaxyIndex,
+            ids: &[usize],
+            depth: usize,
+            galaxy: usize,
+            k: usize,
+            metric: Metric,
+            best: &mut BinaryHeap<(u128, usize)>,
+        ) {
+            if ids.is_empty() {
+                return;
+            }
+            let mid = ids.len() / 2;
+            let here = index.galaxies[galaxy];
+            let split = index.galaxies[ids[mid]];
+            if ids[mid] != galaxy {
+                best.push((metric.key(here, split), ids[mid]));
+                if best.len() > k {
+                    best.pop();
+                }
+            }
+            let (near, far) = if coordinate(here, depth) < coordinate(split, depth) {
+                (&ids[..mid], &ids[mid + 1..])
+            } else {
+                (&ids[mid + 1..], &ids[..mid])
+            };
+            search(index, near, depth + 1, galaxy, k, metric, best);
+            // everything on the far side is at least this far away
+            let gap = coordinate(here, depth).abs_diff(coordinate(split, depth));
+            if best.len() < k || metric.axis_key(gap) <= best.peek().unwrap().0 {
+                search(index, far, depth + 1, galaxy, k, metric, best);
+            }
+        }
+        let mut best = BinaryHeap::new();
+        if k > 0 {
+            search(self, &self.ids, 0, galaxy, k, metric, &mut best);
+        }
+        best.into_sorted_vec()
+    }
+
+    fn nearest(&self, galaxy: usize, metric: Metric) -> Option<(u128, usize)> {
+        self.k_nearest(galaxy, 1, metric).first().copied()
+    }
+
+    // The `k` closest pairs, closest first, as `(key, a, b)` with `a < b`.
+    // Each of them is among the `k` nearest neighbours of its first galaxy.
+    fn k_closest_pairs(&self, k: usize, metric: Metric) -> Vec<(u128, usize, usize)> {
+        let mut pairs: Vec<(u128, usize, usize)> = (0..self.galaxies.len())
+            .flat_map(|a| {
+                self.k_nearest(a, k, metric)
+                    .into_iter()
+                    .map(move |(key, b)| (key, a.min(b), a.max(b)))
+            })
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs.truncate(k);
+        pairs
+    }
 
-    let mut sum_distances = 0;
-    for (i, a) in galaxies.iter().enumerate() {
-        for j in i + 1..galaxies.len() {
-            let b = galaxies[j];
-            let dist = a.0.abs_diff(b.0) + a.1.abs_diff(b.1);
-            sum_distances += dist;
+    // The corners of the convex hull of the galaxies, counter-clockwise.
+    fn hull(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.galaxies.len()).collect();
+        ids.sort_by_key(|id| self.galaxies[*id]);
+        ids.dedup_by_key(|id| self.galaxies[*id]);
+        if ids.len() < 3 {
+            return ids;
         }
+        let cross = |o: usize, a: usize, b: usize| -> i128 {
+            let (o, a, b) = (self.galaxies[o], self.galaxies[a], self.galaxies[b]);
+            (a.0 - o.0) as i128 * (b.1 - o.1) as i128 - (a.1 - o.1) as i128 * (b.0 - o.0) as i128
+        };
+        let mut hull = Vec::<usize>::new();
+        for pass in [ids.clone(), ids.into_iter().rev().collect()] {
+            let start = hull.len();
+            for id in pass {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], id) <= 0
+                {
+                    hull.pop();
+                }
+                hull.push(id);
+            }
+            // the last corner of each half is the first of the other
+            hull.pop();
+        }
+        hull
+    }
+
+    // All three metrics are norms, so the farthest pair is two hull corners.
+    fn farthest_pair(&self, metric: Metric) -> Option<(u128, usize, usize)> {
+        let hull = self.hull();
+        hull.iter()
+            .enumerate()
+            .flat_map(|(i, a)| {
+                hull[i + 1..].iter().map(move |b| {
+                    let key = metric.key(self.galaxies[*a], self.galaxies[*b]);
+                    (key, *a.min(b), *a.max(b))
+                })
+            })
+            .max_by_key(|(key, a, b)| (*key, std::cmp::Reverse((*a, *b))))
     }
 
-    sum_distances
+    // How many galaxies are at most `max_key` away from `galaxy`, itself
+    // included. Subtrees whose box is entirely in or out of range are settled
+    // without visiting their galaxies.
+    fn count_within(&self, galaxy: (i64, i64), max_key: u128, metric: Metric) -> u64 {
+        fn count(
+            index: &GalaxyIndex,
+            from: usize,
+            to: usize,
+            galaxy: (i64, i64),
+            max_key: u128,
+            metric: Metric,
+        ) -> u64 {
+            if from == to {
+                return 0;
+            }
+            let mid = from + (to - from) / 2;
+            let (min_row, max_row, min_col, max_col) = index.boxes[mid];
+            let (near_row, far_row) = gaps(galaxy.0, min_row, max_row);
+            let (near_col, far_col) = gaps(galaxy.1, min_col, max_col);
+            if metric.key_of_gaps(near_row, near_col) > max_key {
+                return 0;
+            }
+            if metric.key_of_gaps(far_row, far_col) <= max_key {
+                return (to - from) as u64;
+            }
+            let here = metric.key(galaxy, index.galaxies[index.ids[mid]]) <= max_key;
+            here as u64
+                + count(index, from, mid, galaxy, max_key, metric)
+                + count(index, mid + 1, to, galaxy, max_key, metric)
+        }
+        count(self, 0, self.ids.len(), galaxy, max_key, metric)
+    }
+
+    // How many pairs are at most `max_key` apart.
+    fn count_pairs_within(&self, max_key: u128, metric: Metric) -> u64 {
+        let with_themselves: u64 = self
+            .galaxies
+            .iter()
+            .map(|g| self.count_within(*g, max_key, metric))
+            .sum();
+        (with_themselves - self.galaxies.len() as u64) / 2
+    }
+
+    // How many pairs fall in each of `num_buckets` equal slices of
+    // `0..=farthest`, as `(low, high, count)`. Every bucket edge is one range
+    // count per galaxy on the tree.
+    fn distribution(&self, num_buckets: usize, metric: Metric) -> Vec<(f64, f64, u64)> {
+        let Some((max_key, _, _)) = self.farthest_pair(metric) else {
+            return vec![];
+        };
+        if num_buckets == 0 {
+            return vec![];
+        }
+        let num_galaxies = self.galaxies.len() as u64;
+        let num_pairs = num_galaxies * (num_galaxies - 1) / 2;
+        let width = metric.distance(max_key) / num_buckets as f64;
+        // `below[i]` pairs are closer than the start of bucket `i`
+        let mut below: Vec<u64> = (0..num_buckets)
+            .map(|i| {
+                if i == 0 || width == 0.0 {
+                    return 0;
+                }
+                metric
+                    .max_key_below(i as f64 * width)
+                    .map_or(0, |key| self.count_pairs_within(key, metric))
+            })
+            .collect();
+        below.push(num_pairs);
+        // with every pair at distance 0 they all go in the first bucket
+        if width == 0.0 {
+            below[1..].fill(num_pairs);
+        }
+        (0..num_buckets)
+            .map(|i| {
+                let count = below[i + 1] - below[i];
+                (i as f64 * width, (i + 1) as f64 * width, count)
+            })
+            .collect()
+    }
 }
 
-fn part_one(content: &str) -> u64 {
+fn part_one(content: &str) -> u128 {
     run_with_multiplier(content, 2)
 }
 
-fn part_two(content: &str) -> u64 {
+fn part_two(content: &str) -> u128 {
     run_with_multiplier(content, 1000000)
 }
 
@@ -59,4 +402,189 @@ fn main() {
 
     let answer2 = part_two(&content);
     println!("the answer for the second part is: {answer2}");
+
+    match env::args().nth(2).as_deref() {
+        // `expand <rows> <cols>` uses a separate factor for each axis.
+        Some("expand") => {
+            let factor = |n: usize| -> i64 { env::args().nth(n).unwrap().parse().unwrap() };
+            let expansion = Expansion {
+                rows: factor(3),
+                cols: factor(4),
+            };
+            let answer = run_with_expansion(&content, expansion);
+            println!("with {expansion:?} the sum of distances is: {answer}");
+        }
+        // `<query> <metric> <multiplier> [n]` asks the spatial index, with
+        // galaxies numbered from 1 in reading order.
+        Some(query @ ("nearest" | "closest" | "farthest" | "distribution")) => {
+            let metric = Metric::from_arg(&env::args().nth(3).unwrap()).unwrap();
+            let multiplier: i64 = env::args().nth(4).unwrap().parse().unwrap();
+            let n: usize = env::args().nth(5).map_or(1, |n| n.parse().unwrap());
+            let galaxies = expanded_galaxies(
+                &parse_grid(&content),
+                Expansion {
+                    rows: multiplier,
+                    cols: multiplier,
+                },
+            );
+            let index = GalaxyIndex::new(galaxies);
+            let distance = |key: u128| metric.distance(key);
+            match query {
+                "nearest" if n == 0 || n > index.galaxies.len() => println!(
+                    "there is no galaxy {n}, they are numbered from 1 to {}",
+                    index.galaxies.len()
+                ),
+                "nearest" => match index.nearest(n - 1, metric) {
+                    Some((key, other)) => println!(
+                        "galaxy {} is closest to galaxy {n}, {} away",
+                        other + 1,
+                        distance(key)
+                    ),
+                    None => println!("galaxy {n} is alone"),
+                },
+                "closest" => {
+                    for (key, a, b) in index.k_closest_pairs(n, metric) {
+                        println!("galaxies {} and {}: {}", a + 1, b + 1, distance(key));
+                    }
+                }
+                "farthest" => {
+                    if let Some((key, a, b)) = index.farthest_pair(metric) {
+                        println!("galaxies {} and {}: {}", a + 1, b + 1, distance(key));
+                    }
+                }
+                _ => {
+                    for (low, high, count) in index.distribution(n, metric) {
+                        println!("{low:.1}..{high:.1}: {count}");
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixed-seed pseudo-random numbers for the random inputs below.
+    struct Lcg {
+        state: u64,
+    }
+
+    impl Lcg {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.state = self
+                .state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.state >> 33) % bound
+        }
+    }
+
+    fn sum_distances_quadratic(galaxies: &[(i64, i64)]) -> u128 {
+        let mut sum_distances = 0;
+        for (i, a) in galaxies.iter().enumerate() {
+            for b in &galaxies[i + 1..] {
+                sum_distances += (a.0.abs_diff(b.0) + a.1.abs_diff(b.1)) as u128;
+            }
+        }
+        sum_distances
+    }
+
+    // The sorted sums against the pairwise loop on random rectangular grids,
+    // with different expansions per axis.
+    #[test]
+    fn sums_match_pairwise_loop() {
+        let mut rng = Lcg {
+            state: 0x2545f4914f6cdd1d,
+        };
+        for _ in 0..1000 {
+            let height = 1 + rng.next(30) as usize;
+            let width = 1 + rng.next(30) as usize;
+            let density = 1 + rng.next(10);
+            let grid: Vec<Vec<char>> = (0..height)
+                .map(|_| {
+                    (0..width)
+                        .map(|_| if rng.next(100) < density { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            let expansion = Expansion {
+                rows: 1 + rng.next(1000000) as i64,
+                cols: 1 + rng.next(1000000) as i64,
+            };
+            let galaxies = expanded_galaxies(&grid, expansion);
+            assert_eq!(
+                sum_distances(&galaxies),
+                sum_distances_quadratic(&galaxies),
+                "{height}x{width} grid with {expansion:?}"
+            );
+        }
+    }
+
+    // The index queries against looking at every pair.
+    #[test]
+    fn queries_match_every_pair() {
+        let mut rng = Lcg {
+            state: 0x2545f4914f6cdd1d,
+        };
+        for _ in 0..1000 {
+            let galaxies: Vec<(i64, i64)> = (0..1 + rng.next(60))
+                .map(|_| (rng.next(40) as i64, rng.next(40) as i64))
+                .collect();
+            let metric =
+                [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean][rng.next(3) as usize];
+            let index = GalaxyIndex::new(galaxies.clone());
+            let mut pairs: Vec<(u128, usize, usize)> = vec![];
+            for (a, ga) in galaxies.iter().enumerate() {
+                for (b, gb) in galaxies.iter().enumerate().skip(a + 1) {
+                    pairs.push((metric.key(*ga, *gb), a, b));
+                }
+            }
+            pairs.sort_unstable();
+
+            for (a, ga) in galaxies.iter().enumerate() {
+                let nearest = galaxies
+                    .iter()
+                    .enumerate()
+                    .filter(|(b, _)| *b != a)
+                    .map(|(_, gb)| metric.key(*ga, *gb))
+                    .min();
+                assert_eq!(index.nearest(a, metric).map(|n| n.0), nearest);
+            }
+            let k = 1 + rng.next(10) as usize;
+            let closest: Vec<u128> = index
+                .k_closest_pairs(k, metric)
+                .iter()
+                .map(|p| p.0)
+                .collect();
+            let expected: Vec<u128> = pairs.iter().take(k).map(|p| p.0).collect();
+            assert_eq!(closest, expected, "{metric:?} {galaxies:?}");
+            assert_eq!(
+                index.farthest_pair(metric).map(|p| p.0),
+                pairs.last().map(|p| p.0),
+                "{metric:?} {galaxies:?}"
+            );
+
+            let num_buckets = 1 + rng.next(8) as usize;
+            let distribution = index.distribution(num_buckets, metric);
+            if galaxies.len() < 2 {
+                assert!(distribution.is_empty());
+                continue;
+            }
+            assert_eq!(distribution.len(), num_buckets);
+            for (i, (low, high, count)) in distribution.into_iter().enumerate() {
+                // the last bucket also holds the farthest pair
+                let in_bucket = |key: u128| -> bool {
+                    let above_low = metric.max_key_below(low).is_none_or(|k| key > k);
+                    let below_high = i + 1 == num_buckets
+                        || metric.max_key_below(high).is_some_and(|k| key <= k);
+                    above_low && below_high
+                };
+                let expected = pairs.iter().filter(|p| in_bucket(p.0)).count() as u64;
+                assert_eq!(count, expected, "{metric:?} {low}..{high} {galaxies:?}");
+            }
+        }
+    }
 }
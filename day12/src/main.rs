@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 use more_asserts::assert_le;
-use std::{env, fs};
+use num::{BigUint, CheckedAdd, CheckedMul, Integer, One, Zero};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, process};
 
 fn parse_comma_separated_nums(line: &str) -> Vec<usize> {
     line.split(",")
@@ -9,55 +13,499 @@ fn parse_comma_separated_nums(line: &str) -> Vec<usize> {
         .collect()
 }
 
-fn calc_for_line(line: &str, num_repeats: usize) -> u64 {
+// Anything the arrangements can be counted in, `u128` or `BigUint`. Counts
+// grow exponentially with unfolding, so running out of bits is a panic rather
+// than a silently wrapped answer.
+trait Count: Clone + Zero + One + CheckedAdd + CheckedMul {
+    fn add_count(&mut self, other: &Self) {
+        *self = self.checked_add(other).expect("the count overflowed");
+    }
+
+    fn times(&self, other: &Self) -> Self {
+        self.checked_mul(other).expect("the count overflowed")
+    }
+}
+
+impl<T: Clone + Zero + One + CheckedAdd + CheckedMul> Count for T {}
+
+fn from_bool<T: Count>(b: bool) -> T {
+    if b {
+        T::one()
+    } else {
+        T::zero()
+    }
+}
+
+fn parse_line(line: &str) -> (&str, Vec<usize>) {
     let (springs_str, nums_str) = line.split_once(" ").unwrap();
-    let springs_str = vec![springs_str; num_repeats].join("?");
-    let nums_str = vec![nums_str; num_repeats].join(",");
-    let nums = parse_comma_separated_nums(&nums_str);
+    (springs_str, parse_comma_separated_nums(nums_str))
+}
 
-    let bytes = springs_str.as_bytes();
+// `rows[i][j]` is the number of ways to place the groups `nums[i..]` in
+// `bytes[j..]`. Row `i` only needs row `i + 1`, so only those two are kept.
+fn count_arrangements<T: Count>(bytes: &[u8], nums: &[usize]) -> T {
+    if nums.is_empty() {
+        return from_bool(!bytes.contains(&b'#'));
+    }
     let can_be_dot =
         |pos: usize| -> bool { pos < bytes.len() && (bytes[pos] == b'.' || bytes[pos] == b'?') };
     let can_be_spring =
         |pos: usize| -> bool { pos < bytes.len() && (bytes[pos] == b'#' || bytes[pos] == b'?') };
 
     // The +1 is a neat trick to avoid checking the indices vs `bytes.len()`.
-    let mut dp_table: Vec<Vec<u64>> = vec![vec![0; bytes.len() + 1]; nums.len()];
+    let mut next_row: Vec<T> = vec![T::zero(); bytes.len() + 1];
+    let mut row: Vec<T> = vec![T::zero(); bytes.len() + 1];
     for i in (0..nums.len()).rev() {
+        row[bytes.len()] = T::zero();
         for j in (0..bytes.len()).rev() {
-            if bytes[j] == b'.' || bytes[j] == b'?' {
-                dp_table[i][j] = dp_table[i][j + 1];
-            }
+            row[j] = if bytes[j] == b'.' || bytes[j] == b'?' {
+                row[j + 1].clone()
+            } else {
+                T::zero()
+            };
             let this_is_the_last_number = i + 1 == nums.len();
             if bytes[j] == b'#' || bytes[j] == b'?' {
                 let k = j + nums[i];
                 if (j..k).all(can_be_spring) {
                     assert_le!(k, bytes.len()); // otherwise `all(can_be_spring)` wouldn't match
-                    dp_table[i][j] += if k == bytes.len() {
+                    let ways = if k == bytes.len() {
                         // Stopped at exactly the end of string.
                         // If this was the last number to be satisfied, then we're good.
                         // Otherwise there are more numbers to satisfy, but no chars left => UNSAT.
-                        this_is_the_last_number as u64
+                        from_bool(this_is_the_last_number)
                     } else if can_be_dot(k) {
                         // Stopped at a dot.
                         if this_is_the_last_number {
                             // The rest should be dots or question marks.
-                            (k + 1..bytes.len()).all(can_be_dot) as u64
+                            from_bool((k + 1..bytes.len()).all(can_be_dot))
                         } else {
                             // There are more numbers to go through.
-                            dp_table[i + 1][k + 1]
+                            next_row[k + 1].clone()
                         }
                     } else {
-                        0
+                        T::zero()
+                    };
+                    row[j].add_count(&ways);
+                }
+            }
+        }
+        std::mem::swap(&mut row, &mut next_row);
+    }
+    next_row[0].clone()
+}
+
+fn calc_for_line(line: &str, num_repeats: usize) -> u128 {
+    let (springs_str, nums) = parse_line(line);
+    let springs_str = vec![springs_str; num_repeats].join("?");
+    count_arrangements(springs_str.as_bytes(), &nums.repeat(num_repeats))
+}
+
+// Reading the springs left to right, the state is which group comes next
+// (only its index mod `groups.len()` matters) and how many of its springs
+// have been placed, `run`. A run of the full length still needs a dot.
+struct Automaton {
+    groups: Vec<usize>,
+    // `offsets[c]` is the state of group `c` with `run == 0`.
+    offsets: Vec<usize>,
+    num_states: usize,
+}
+
+impl Automaton {
+    fn new(groups: Vec<usize>) -> Automaton {
+        let mut offsets = vec![];
+        let mut num_states = 0;
+        for size in &groups {
+            offsets.push(num_states);
+            num_states += size + 1;
+        }
+        Automaton {
+            groups,
+            offsets,
+            num_states,
+        }
+    }
+
+    fn decode(&self, state: usize) -> (usize, usize) {
+        let c = self.offsets.partition_point(|offset| *offset <= state) - 1;
+        (c, state - self.offsets[c])
+    }
+
+    // The states after reading `c`, with whether a group was finished.
+    fn step(&self, state: usize, c: u8) -> Vec<(usize, usize)> {
+        let (group, run) = self.decode(state);
+        let mut next = vec![];
+        if c == b'.' || c == b'?' {
+            if run == 0 {
+                next.push((0, state));
+            } else if run == self.groups[group] {
+                next.push((1, self.offsets[(group + 1) % self.groups.len()]));
+            }
+        }
+        if (c == b'#' || c == b'?') && run < self.groups[group] {
+            next.push((0, state + 1));
+        }
+        next
+    }
+}
+
+// Counts indexed by the number of finished groups, stored from the lowest one
+// that can happen: after many copies of a row with little slack they all
+// bunch up around the same number.
+#[derive(Debug, Clone)]
+struct Counts<T> {
+    low: usize,
+    counts: Vec<T>,
+}
+
+impl<T: Count> Counts<T> {
+    fn empty() -> Counts<T> {
+        Counts {
+            low: 0,
+            counts: vec![],
+        }
+    }
+
+    fn get(&self, finished: usize) -> T {
+        finished
+            .checked_sub(self.low)
+            .and_then(|i| self.counts.get(i))
+            .cloned()
+            .unwrap_or_else(T::zero)
+    }
+
+    fn add_at(&mut self, finished: usize, count: &T) {
+        if self.counts.is_empty() {
+            self.low = finished;
+        } else if finished < self.low {
+            let mut counts = vec![T::zero(); self.low - finished];
+            counts.append(&mut self.counts);
+            self.counts = counts;
+            self.low = finished;
+        }
+        let i = finished - self.low;
+        if self.counts.len() <= i {
+            self.counts.resize(i + 1, T::zero());
+        }
+        self.counts[i].add_count(count);
+    }
+
+    // Drops the counts for finishing fewer than `low` or more than `high` groups.
+    fn restrict(&mut self, low: usize, high: usize) {
+        let end = (high + 1).saturating_sub(self.low).min(self.counts.len());
+        self.counts.truncate(end);
+        let start = low.saturating_sub(self.low).min(self.counts.len());
+        self.counts.drain(..start);
+        self.low += start;
+        while self.counts.first().is_some_and(|c| c.is_zero()) {
+            self.counts.remove(0);
+            self.low += 1;
+        }
+        if self.counts.is_empty() {
+            self.low = 0;
+        }
+    }
+}
+
+// `entries[from][to]` counts the ways of reading some springs that go from
+// state `from` to state `to`, by how many groups they finish.
+#[derive(Debug, Clone)]
+struct Transfer<T> {
+    entries: Vec<Vec<Counts<T>>>,
+}
+
+impl<T: Count> Transfer<T> {
+    fn identity(num_states: usize) -> Transfer<T> {
+        let mut entries = vec![vec![Counts::empty(); num_states]; num_states];
+        for (state, entry) in entries.iter_mut().enumerate() {
+            entry[state].add_at(0, &T::one());
+        }
+        Transfer { entries }
+    }
+
+    fn of_springs(automaton: &Automaton, bytes: &[u8], max_finished: usize) -> Transfer<T> {
+        let num_states = automaton.num_states;
+        let mut transfer: Transfer<T> = Transfer::identity(num_states);
+        for entry in transfer.entries.iter_mut() {
+            for c in bytes {
+                let mut next: Vec<Counts<T>> = vec![Counts::empty(); num_states];
+                for (state, counts) in entry.iter().enumerate() {
+                    for (step_finished, next_state) in automaton.step(state, *c) {
+                        for (i, count) in counts.counts.iter().enumerate() {
+                            let finished = counts.low + i + step_finished;
+                            if finished <= max_finished {
+                                next[next_state].add_at(finished, count);
+                            }
+                        }
                     }
                 }
+                *entry = next;
             }
         }
+        transfer
+    }
+
+    // Reading the springs of `self` and then those of `other`. Paths that
+    // finish more than `max_finished` groups can never be completed.
+    fn then(&self, other: &Transfer<T>, max_finished: usize) -> Transfer<T> {
+        let num_states = other.entries.len();
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut composed: Vec<Counts<T>> = vec![Counts::empty(); num_states];
+                for (middle, counts) in entry.iter().enumerate() {
+                    for (to, more_counts) in other.entries[middle].iter().enumerate() {
+                        for (i, count) in counts.counts.iter().enumerate() {
+                            if count.is_zero() {
+                                continue;
+                            }
+                            for (j, more_count) in more_counts.counts.iter().enumerate() {
+                                let finished = counts.low + i + more_counts.low + j;
+                                if finished > max_finished {
+                                    break;
+                                }
+                                composed[to].add_at(finished, &count.times(more_count));
+                            }
+                        }
+                    }
+                }
+                composed
+            })
+            .collect();
+        Transfer { entries }
+    }
+
+    fn restrict(&mut self, low: usize, high: usize) {
+        for counts in self.entries.iter_mut().flatten() {
+            counts.restrict(low, high);
+        }
+    }
+
+    // The fewest and most groups any path finishes.
+    fn finished_range(&self) -> (usize, usize) {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|counts| !counts.counts.is_empty())
+            .map(|counts| (counts.low, counts.low + counts.counts.len() - 1))
+            .fold((usize::MAX, 0), |(low, high), (l, h)| {
+                (low.min(l), high.max(h))
+            })
     }
-    dp_table[0][0]
 }
 
-fn part_one(content: &str) -> u64 {
+// Same as `calc_for_line`, but the `num_repeats` copies of the springs are
+// never joined: the transfer through one `?` and a copy is raised to the
+// power `num_repeats - 1` by repeated squaring. Every product only keeps the
+// numbers of finished groups that the copies outside it can still complete to
+// the total. Rows where every copy holds about the same number of groups stay
+// cheap for any number of copies; rows with a lot of slack still keep a window
+// that grows with the number of copies.
+fn calc_for_line_unfolded<T: Count>(line: &str, num_repeats: usize) -> T {
+    let (springs_str, groups) = parse_line(line);
+    if num_repeats == 0 || groups.is_empty() {
+        return from_bool(num_repeats == 0 || !springs_str.contains('#'));
+    }
+    let num_groups = groups.len() * num_repeats;
+    let automaton = Automaton::new(groups);
+    let mut all = Transfer::<T>::of_springs(&automaton, springs_str.as_bytes(), num_groups);
+    // only the paths from the start state matter
+    all.entries.truncate(1);
+    let copy = Transfer::of_springs(&automaton, format!("?{springs_str}").as_bytes(), num_groups);
+    let (first_min, first_max) = all.finished_range();
+    let (copy_min, copy_max) = copy.finished_range();
+    if first_min == usize::MAX || copy_min == usize::MAX {
+        return T::zero();
+    }
+
+    // The window for `copies` copies of `?` and the springs, when the first
+    // copy is (`with_first`) or isn't part of them. The last group may still be
+    // running at the end, so one fewer finished group can be enough.
+    let window = |copies: usize, with_first: bool| -> (usize, usize) {
+        let (mut rest_min, mut rest_max) = (0, 0);
+        if !with_first {
+            (rest_min, rest_max) = (first_min, first_max);
+        }
+        let others = num_repeats - 1 - copies;
+        rest_min += others * copy_min;
+        rest_max += others * copy_max;
+        (
+            (num_groups - 1).saturating_sub(rest_max),
+            num_groups.saturating_sub(rest_min),
+        )
+    };
+
+    let (low, high) = window(0, true);
+    all.restrict(low, high);
+    let mut square = copy;
+    let mut square_copies = 1;
+    let mut done = 0;
+    let mut exponent = num_repeats - 1;
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            done += square_copies;
+            let (low, high) = window(done, true);
+            all = all.then(&square, high);
+            all.restrict(low, high);
+        }
+        exponent /= 2;
+        if exponent > 0 {
+            square_copies *= 2;
+            let (low, high) = window(square_copies, false);
+            square = square.then(&square, high);
+            square.restrict(low, high);
+        }
+    }
+
+    // Either every group was closed by a dot, or the last one runs to the end.
+    let last = automaton.groups.len() - 1;
+    let last_run_done = automaton.offsets[last] + automaton.groups[last];
+    let mut total = all.entries[0][0].get(num_groups);
+    total.add_count(&all.entries[0][last_run_done].get(num_groups - 1));
+    total
+}
+
+// Every way of placing the groups `nums[i..]` in `bytes[j..]`, counted in
+// `ways[i][j]`, so that concrete arrangements can be listed, ranked and drawn
+// without trying the dead ends.
+struct ArrangementTable<T> {
+    bytes: Vec<u8>,
+    nums: Vec<usize>,
+    ways: Vec<Vec<T>>,
+}
+
+impl<T: Count + Integer + From<u32>> ArrangementTable<T> {
+    fn new(line: &str, num_repeats: usize) -> ArrangementTable<T> {
+        let (springs_str, nums) = parse_line(line);
+        let bytes = vec![springs_str; num_repeats].join("?").into_bytes();
+        let nums = nums.repeat(num_repeats);
+        let mut table = ArrangementTable {
+            ways: vec![vec![T::zero(); bytes.len() + 2]; nums.len() + 1],
+            bytes,
+            nums,
+        };
+        let len = table.bytes.len();
+        for i in (0..=table.nums.len()).rev() {
+            table.ways[i][len] = from_bool(i == table.nums.len());
+            for j in (0..len).rev() {
+                let mut ways = T::zero();
+                if table.can_be_dot(j) {
+                    ways.add_count(&table.ways[i][j + 1]);
+                }
+                if let Some(next) = table.after_group(i, j) {
+                    ways.add_count(&table.ways[i + 1][next]);
+                }
+                table.ways[i][j] = ways;
+            }
+        }
+        table
+    }
+
+    fn can_be_dot(&self, pos: usize) -> bool {
+        self.bytes[pos] == b'.' || self.bytes[pos] == b'?'
+    }
+
+    // Where to carry on from if group `i` starts at `j`, past the dot that
+    // ends it, or `None` if it doesn't fit there.
+    fn after_group(&self, i: usize, j: usize) -> Option<usize> {
+        let k = j + *self.nums.get(i)?;
+        let fits = k <= self.bytes.len()
+            && self.bytes[j..k].iter().all(|b| *b == b'#' || *b == b'?')
+            && (k == self.bytes.len() || self.can_be_dot(k));
+        fits.then_some((k + 1).min(self.bytes.len()))
+    }
+
+    fn total(&self) -> T {
+        self.ways[0][0].clone()
+    }
+
+    // The arrangement with `rank` arrangements before it in lexicographic
+    // order, where `#` comes before `.` as in ASCII.
+    fn nth(&self, mut rank: T) -> Option<String> {
+        if rank >= self.total() {
+            return None;
+        }
+        let mut out = String::new();
+        let (mut i, mut j) = (0, 0);
+        while j < self.bytes.len() {
+            let group_first = self
+                .after_group(i, j)
+                .map_or(T::zero(), |next| self.ways[i + 1][next].clone());
+            if rank < group_first {
+                let next = self.after_group(i, j).unwrap();
+                out += &"#".repeat(self.nums[i]);
+                out += &".".repeat(next - j - self.nums[i]);
+                i += 1;
+                j = next;
+            } else {
+                rank = rank - group_first;
+                out.push('.');
+                j += 1;
+            }
+        }
+        Some(out)
+    }
+
+    // All the arrangements in lexicographic order, built only when asked for.
+    fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        let next = |rank: &T| {
+            let mut next = rank.clone();
+            next.add_count(&T::one());
+            Some(next)
+        };
+        std::iter::successors(Some(T::zero()), next).map_while(|rank| self.nth(rank))
+    }
+
+    // Every arrangement is equally likely.
+    fn sample(&self, rng: &mut Lcg) -> Option<String> {
+        if self.total().is_zero() {
+            return None;
+        }
+        self.nth(rng.below(&self.total()))
+    }
+}
+
+// Random numbers for `sample`. The constants are fixed here rather than left
+// to a library, so the same seed draws the same arrangements on any build.
+struct Lcg {
+    state: u64,
+}
+
+impl Lcg {
+    // 31 random bits.
+    fn next(&mut self) -> u64 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.state >> 33
+    }
+
+    // Uniform in `0..bound`: as many random bits as `bound - 1` has, redrawn
+    // when they come out too big, which is less than half of the time.
+    fn below<T: Integer + Clone + From<u32>>(&mut self, bound: &T) -> T {
+        let max = bound.clone() - T::one();
+        let mut num_bits = 0;
+        let mut rest = max.clone();
+        while !rest.is_zero() {
+            rest = rest / T::from(2);
+            num_bits += 1;
+        }
+        loop {
+            let mut value = T::zero();
+            for chunk_start in (0..num_bits).step_by(16) {
+                let chunk = (num_bits - chunk_start).min(16);
+                let bits = (self.next() & ((1 << chunk) - 1)) as u32;
+                value = value * T::from(1 << chunk) + T::from(bits);
+            }
+            if value <= max {
+                return value;
+            }
+        }
+    }
+}
+
+fn part_one(content: &str) -> u128 {
     content
         .split("\n")
         .filter(|l| !l.is_empty())
@@ -65,7 +513,7 @@ fn part_one(content: &str) -> u64 {
         .sum()
 }
 
-fn part_two(content: &str) -> u64 {
+fn part_two(content: &str) -> u128 {
     content
         .split("\n")
         .filter(|l| !l.is_empty())
@@ -73,6 +521,14 @@ fn part_two(content: &str) -> u64 {
         .sum()
 }
 
+// The `n`th command line argument, or `default` when it's missing.
+fn arg<A: FromStr>(n: usize, default: A) -> A
+where
+    A::Err: Debug,
+{
+    env::args().nth(n).map_or(default, |a| a.parse().unwrap())
+}
+
 fn main() {
     let file_path = env::args().nth(1).unwrap();
     let content = fs::read_to_string(file_path).unwrap();
@@ -82,4 +538,199 @@ fn main() {
 
     let answer2 = part_two(&content);
     println!("the answer for the second part is: {answer2}");
+
+    match env::args().nth(2).as_deref() {
+        // `unfold <n>` unfolds every row `n` times, without joining the copies.
+        // Tight rows take about the same time for any `n`, but rows with a lot
+        // of slack slow down with roughly `n^2`: on `real.txt` 50 takes about
+        // 10s and 100 about 50s in release builds, so 200 and up is impractical.
+        Some("unfold") => {
+            let Some(num_repeats) = env::args().nth(3).and_then(|a| a.parse::<usize>().ok()) else {
+                eprintln!(
+                    "usage: day12 <input> unfold <n>, where n is the number of copies \
+                     (up to about 100 is practical on real inputs)"
+                );
+                process::exit(1);
+            };
+            let answer: BigUint = content
+                .split("\n")
+                .filter(|l| !l.is_empty())
+                .map(|l| calc_for_line_unfolded::<BigUint>(l, num_repeats))
+                .sum();
+            println!("unfolded {num_repeats} times: {answer}");
+        }
+        // `list <row> [repeats] [limit]`, `nth <row> <rank> [repeats]` and
+        // `sample <row> <count> [repeats] [seed]` show concrete arrangements of
+        // one row; rows are numbered from 1 and ranks from 0. Without a seed,
+        // `sample` seeds from the clock and prints the seed to replay it.
+        Some(mode @ ("list" | "nth" | "sample")) => {
+            let row: usize = arg(3, 1);
+            let line = content
+                .split("\n")
+                .filter(|l| !l.is_empty())
+                .nth(row - 1)
+                .unwrap();
+            match mode {
+                "list" => {
+                    let table = ArrangementTable::<BigUint>::new(line, arg(4, 1));
+                    for arrangement in table.iter().take(arg(5, usize::MAX)) {
+                        println!("{arrangement}");
+                    }
+                }
+                "nth" => {
+                    let table = ArrangementTable::<BigUint>::new(line, arg(5, 1));
+                    match table.nth(arg(4, BigUint::zero())) {
+                        Some(arrangement) => println!("{arrangement}"),
+                        None => println!("there are only {} arrangements", table.total()),
+                    }
+                }
+                _ => {
+                    let table = ArrangementTable::<BigUint>::new(line, arg(5, 1));
+                    let seed = env::args().nth(6).map_or_else(
+                        || {
+                            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                            now.as_nanos() as u64
+                        },
+                        |a| a.parse().unwrap(),
+                    );
+                    eprintln!("sampling with seed {seed}");
+                    let mut rng = Lcg { state: seed };
+                    for _ in 0..arg(4, 1) {
+                        match table.sample(&mut rng) {
+                            Some(arrangement) => println!("{arrangement}"),
+                            None => println!("there are no arrangements"),
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Display;
+
+    // Compares composing copies against joining them, in `u128` up to
+    // `max_repeats` and in `BigUint` for `big_repeats`, past what `u128` holds.
+    fn check_unfolded(content: &str, max_repeats: usize, big_repeats: usize) {
+        for line in content.split("\n").filter(|l| !l.is_empty()) {
+            for num_repeats in 0..=max_repeats {
+                assert_eq!(
+                    calc_for_line_unfolded::<u128>(line, num_repeats),
+                    calc_for_line(line, num_repeats),
+                    "{line} unfolded {num_repeats} times"
+                );
+            }
+            let (springs_str, nums) = parse_line(line);
+            let joined = vec![springs_str; big_repeats].join("?");
+            assert_eq!(
+                calc_for_line_unfolded::<BigUint>(line, big_repeats),
+                count_arrangements::<BigUint>(joined.as_bytes(), &nums.repeat(big_repeats)),
+                "{line} unfolded {big_repeats} times"
+            );
+        }
+    }
+
+    // Whether `candidate` fills in the unknowns of `springs_str` and its runs of
+    // damaged springs are exactly `nums`.
+    fn is_arrangement(springs_str: &str, nums: &[usize], candidate: &str) -> bool {
+        let fills_in = springs_str.len() == candidate.len()
+            && springs_str
+                .bytes()
+                .zip(candidate.bytes())
+                .all(|(s, c)| (c == b'#' || c == b'.') && (s == b'?' || s == c));
+        let runs: Vec<usize> = candidate
+            .split('.')
+            .filter(|run| !run.is_empty())
+            .map(|run| run.len())
+            .collect();
+        fills_in && runs == nums
+    }
+
+    // Spot checks ranks and samples, past what can be listed.
+    fn check_ranks<T: Count + Integer + From<u32> + Display>(
+        line: &str,
+        num_repeats: usize,
+        rng: &mut Lcg,
+    ) {
+        let (springs_str, nums) = parse_line(line);
+        let springs_str = vec![springs_str; num_repeats].join("?");
+        let nums = nums.repeat(num_repeats);
+        let table = ArrangementTable::<T>::new(line, num_repeats);
+        let total = table.total();
+        assert!(table.nth(total.clone()).is_none(), "{line}");
+        for _ in 0..10 {
+            let rank = rng.below(&total);
+            let a = table.nth(rank.clone()).unwrap();
+            let b = table.nth(rank.clone() + T::one());
+            assert!(is_arrangement(&springs_str, &nums, &a), "{line}");
+            assert!(b.is_none_or(|b| a < b), "{line} at {rank}");
+            let sample = table.sample(rng).unwrap();
+            assert!(is_arrangement(&springs_str, &nums, &sample), "{line}");
+        }
+    }
+
+    #[test]
+    fn arrangements_match_counts() {
+        let mut rng = Lcg { state: 12 };
+        for name in ["example.txt", "real.txt"] {
+            let path = format!("{}/{name}", env!("CARGO_MANIFEST_DIR"));
+            for line in fs::read_to_string(path)
+                .unwrap()
+                .split("\n")
+                .filter(|l| !l.is_empty())
+            {
+                let (springs_str, nums) = parse_line(line);
+                let table = ArrangementTable::<u128>::new(line, 1);
+                assert_eq!(table.total(), calc_for_line(line, 1), "{line}");
+                let all: Vec<String> = table.iter().collect();
+                assert!(
+                    all.windows(2).all(|w| w[0] < w[1]),
+                    "{line} is out of order"
+                );
+                assert!(
+                    all.iter().all(|a| is_arrangement(springs_str, &nums, a)),
+                    "{line}"
+                );
+                for _ in 0..10 {
+                    let sample = table.sample(&mut rng).unwrap();
+                    assert!(
+                        all.binary_search(&sample).is_ok(),
+                        "{line} sampled {sample}"
+                    );
+                }
+                check_ranks::<u128>(line, 5, &mut rng);
+            }
+        }
+    }
+
+    #[test]
+    fn arrangements_past_u128() {
+        let mut rng = Lcg { state: 12 };
+        let path = format!("{}/example.txt", env!("CARGO_MANIFEST_DIR"));
+        for line in fs::read_to_string(path)
+            .unwrap()
+            .split("\n")
+            .filter(|l| !l.is_empty())
+        {
+            let table = ArrangementTable::<BigUint>::new(line, 40);
+            assert_eq!(
+                table.total(),
+                calc_for_line_unfolded::<BigUint>(line, 40),
+                "{line}"
+            );
+            check_ranks::<BigUint>(line, 40, &mut rng);
+        }
+    }
+
+    #[test]
+    fn unfolding_matches_joining() {
+        for (name, big_repeats) in [("example.txt", 40), ("real.txt", 15)] {
+            let path = format!("{}/{name}", env!("CARGO_MANIFEST_DIR"));
+            check_unfolded(&fs::read_to_string(path).unwrap(), 5, big_repeats);
+        }
+    }
 }
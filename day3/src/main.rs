@@ -75,44 +75,44 @@ fn parse_line(line_str: &str) -> Line {
     }
 }
 
-fn part_one(content: &str) -> u32 {
-    fn check_line(line: &Line, prev_line: Option<&Line>, next_line: Option<&Line>) -> u32 {
-        line.numbers
-            .iter()
-            .map(|number| -> u32 {
-                if line.has_matching_symbol(number)
-                    || (prev_line.is_some() && prev_line.unwrap().has_matching_symbol(number))
-                    || (next_line.is_some() && next_line.unwrap().has_matching_symbol(number))
-                {
-                    return number.value;
-                }
-                0
-            })
-            .sum()
-    }
+fn is_part_number(lines: &[Line], line_index: usize, number: &Number) -> bool {
+    (line_index > 0 && lines[line_index - 1].has_matching_symbol(number))
+        || lines[line_index].has_matching_symbol(number)
+        // The overflow here is fine, `get` will take care of it.
+        || lines
+            .get(line_index + 1)
+            .is_some_and(|line| line.has_matching_symbol(number))
+}
 
+fn part_one(content: &str) -> u32 {
     let lines: Vec<Line> = content.split("\n").map(parse_line).collect();
     lines
         .iter()
         .enumerate()
         .map(|(index, line)| -> u32 {
-            check_line(
-                line,
-                if index > 0 {
-                    lines.get(index - 1)
-                } else {
-                    None
-                },
-                // The overflow here is fine, `get` will take care of it.
-                lines.get(index + 1),
-            )
+            line.numbers
+                .iter()
+                .filter(|number| is_part_number(&lines, index, number))
+                .map(|number| number.value)
+                .sum::<u32>()
         })
         .sum()
 }
 
-fn part_two(content: &str) -> u32 {
-    let lines: Vec<Line> = content.split("\n").map(parse_line).collect();
+#[derive(Debug)]
+struct Gear {
+    line_index: usize,
+    star_index: usize,
+    values: (u32, u32),
+}
 
+impl Gear {
+    fn ratio(&self) -> u32 {
+        self.values.0 * self.values.1
+    }
+}
+
+fn find_gears(lines: &[Line]) -> Vec<Gear> {
     fn push_values_matching_star(star_index: usize, line: &Line, matching_values: &mut Vec<u32>) {
         line.numbers.iter().for_each(|number| {
             if symbol_matches_number(star_index, number) {
@@ -120,38 +120,179 @@ fn part_two(content: &str) -> u32 {
             }
         })
     }
-    lines
-        .iter()
-        .enumerate()
-        .map(|(line_index, line)| -> u32 {
-            line.star_indices
-                .iter()
-                .map(|star_index| -> u32 {
-                    let mut matching_values = Vec::<u32>::new();
-                    if line_index > 0 {
-                        push_values_matching_star(
-                            *star_index,
-                            &lines[line_index - 1],
-                            &mut matching_values,
-                        );
-                    }
-                    push_values_matching_star(*star_index, &line, &mut matching_values);
-                    if line_index + 1 < lines.len() {
-                        push_values_matching_star(
-                            *star_index,
-                            &lines[line_index + 1],
-                            &mut matching_values,
-                        )
-                    }
-                    if matching_values.len() == 2 {
-                        matching_values[0] * matching_values[1]
-                    } else {
-                        0
-                    }
-                })
-                .sum()
-        })
-        .sum()
+
+    let mut gears = Vec::<Gear>::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        for star_index in line.star_indices.iter() {
+            let mut matching_values = Vec::<u32>::new();
+            if line_index > 0 {
+                push_values_matching_star(
+                    *star_index,
+                    &lines[line_index - 1],
+                    &mut matching_values,
+                );
+            }
+            push_values_matching_star(*star_index, line, &mut matching_values);
+            if line_index + 1 < lines.len() {
+                push_values_matching_star(*star_index, &lines[line_index + 1], &mut matching_values)
+            }
+            if matching_values.len() == 2 {
+                gears.push(Gear {
+                    line_index,
+                    star_index: *star_index,
+                    values: (matching_values[0], matching_values[1]),
+                });
+            }
+        }
+    }
+    gears
+}
+
+fn part_two(content: &str) -> u32 {
+    let lines: Vec<Line> = content.split("\n").map(parse_line).collect();
+    find_gears(&lines).iter().map(Gear::ratio).sum()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RenderFormat {
+    Ansi,
+    Html,
+}
+
+impl RenderFormat {
+    fn from_arg(arg: &str) -> RenderFormat {
+        match arg {
+            "ansi" => RenderFormat::Ansi,
+            "html" => RenderFormat::Html,
+            _ => panic!("unknown render format: {arg}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Highlight {
+    None,
+    PartNumber,
+    OtherNumber,
+    Gear,
+}
+
+impl Highlight {
+    fn ansi_code(&self) -> &'static str {
+        match self {
+            Highlight::None => "",
+            Highlight::PartNumber => "\x1b[1;32m",
+            Highlight::OtherNumber => "\x1b[2;31m",
+            Highlight::Gear => "\x1b[1;33m",
+        }
+    }
+
+    fn html_class(&self) -> &'static str {
+        match self {
+            Highlight::None => "",
+            Highlight::PartNumber => "part",
+            Highlight::OtherNumber => "other",
+            Highlight::Gear => "gear",
+        }
+    }
+}
+
+fn open_highlight(highlight: Highlight, format: RenderFormat) -> String {
+    match format {
+        RenderFormat::Ansi => highlight.ansi_code().to_string(),
+        RenderFormat::Html => format!("<span class=\"{}\">", highlight.html_class()),
+    }
+}
+
+fn close_highlight(format: RenderFormat) -> &'static str {
+    match format {
+        RenderFormat::Ansi => "\x1b[0m",
+        RenderFormat::Html => "</span>",
+    }
+}
+
+fn escape_html(c: char) -> String {
+    match c {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+// Re-emits the schematic with part numbers, non-part numbers and gears
+// highlighted, followed by a legend listing every gear.
+fn render(content: &str, format: RenderFormat) -> String {
+    let line_strs: Vec<&str> = content
+        .split("\n")
+        .map(|l| l.trim_end_matches('\r'))
+        .collect();
+    let lines: Vec<Line> = line_strs.iter().map(|l| parse_line(l)).collect();
+    let gears = find_gears(&lines);
+
+    let mut out = String::new();
+    if let RenderFormat::Html = format {
+        out.push_str("<style>.part{color:green;font-weight:bold}.other{color:red}");
+        out.push_str(".gear{background:yellow;font-weight:bold}</style>\n<pre>\n");
+    }
+    for (line_index, (line_str, line)) in line_strs.iter().zip(lines.iter()).enumerate() {
+        let mut highlights = vec![Highlight::None; line_str.len()];
+        for number in line.numbers.iter() {
+            let highlight = if is_part_number(&lines, line_index, number) {
+                Highlight::PartNumber
+            } else {
+                Highlight::OtherNumber
+            };
+            highlights[number.start_index..number.end_index].fill(highlight);
+        }
+        for gear in gears.iter().filter(|g| g.line_index == line_index) {
+            highlights[gear.star_index] = Highlight::Gear;
+        }
+
+        let mut current = Highlight::None;
+        for (c, highlight) in line_str.chars().zip(highlights) {
+            if highlight != current {
+                if current != Highlight::None {
+                    out.push_str(close_highlight(format));
+                }
+                if highlight != Highlight::None {
+                    out.push_str(&open_highlight(highlight, format));
+                }
+                current = highlight;
+            }
+            match format {
+                RenderFormat::Ansi => out.push(c),
+                RenderFormat::Html => out.push_str(&escape_html(c)),
+            }
+        }
+        if current != Highlight::None {
+            out.push_str(close_highlight(format));
+        }
+        out.push('\n');
+    }
+    if let RenderFormat::Html = format {
+        out.push_str("</pre>\n<ul>\n");
+    } else {
+        out.push('\n');
+    }
+    for gear in gears.iter() {
+        let legend = format!(
+            "gear at line {}, column {}: {} * {} = {}",
+            gear.line_index + 1,
+            gear.star_index + 1,
+            gear.values.0,
+            gear.values.1,
+            gear.ratio()
+        );
+        match format {
+            RenderFormat::Ansi => out.push_str(&format!("{legend}\n")),
+            RenderFormat::Html => out.push_str(&format!("<li>{legend}</li>\n")),
+        }
+    }
+    if let RenderFormat::Html = format {
+        out.push_str("</ul>\n");
+    }
+    out
 }
 
 fn main() {
@@ -162,4 +303,8 @@ fn main() {
     println!("the answer for the first part is: {answer1}");
     let answer2 = part_two(&content);
     println!("the answer for the second part is: {answer2}");
+
+    if let Some(format_arg) = env::args().nth(2) {
+        print!("{}", render(&content, RenderFormat::from_arg(&format_arg)));
+    }
 }
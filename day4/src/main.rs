@@ -1,7 +1,29 @@
 #![allow(dead_code)]
-use std::{env, fs};
+use std::{collections::HashSet, env, fs};
 
-fn get_num_winners(line: &str) -> u32 {
+fn parse_space_separated_nums(line: &str) -> Vec<u32> {
+    line.split(" ")
+        .filter(|chunk| !chunk.is_empty())
+        .map(|chunk| -> u32 { chunk.parse().unwrap() })
+        .collect()
+}
+
+#[derive(Debug)]
+struct Card {
+    winning_nums: HashSet<u32>,
+    our_nums: Vec<u32>,
+}
+
+impl Card {
+    fn num_matches(&self) -> u32 {
+        self.our_nums
+            .iter()
+            .filter(|num| self.winning_nums.contains(num))
+            .count() as u32
+    }
+}
+
+fn parse_card(line: &str) -> Card {
     let (winning_nums_str, our_nums_str) = line
         .split_once(":")
         .expect("line should contain :")
@@ -9,61 +31,237 @@ fn get_num_winners(line: &str) -> u32 {
         .split_once("|")
         .expect("line should contain |");
 
-    let mut winning_nums: Vec<u32> = winning_nums_str
-        .split(" ")
-        .filter(|chunk| !chunk.is_empty())
-        .map(|chunk| -> u32 { chunk.parse().unwrap() })
-        .collect();
-    winning_nums.sort();
-
-    our_nums_str
-        .split(" ")
-        .filter(|chunk| !chunk.is_empty())
-        .map(|chunk| -> u32 { chunk.parse().unwrap() })
-        .map(|num| -> u32 { winning_nums.binary_search(&num).is_ok() as u32 })
-        .sum()
+    Card {
+        winning_nums: parse_space_separated_nums(winning_nums_str)
+            .into_iter()
+            .collect(),
+        our_nums: parse_space_separated_nums(our_nums_str),
+    }
 }
 
-fn part_one(content: &str) -> u32 {
+fn parse_cards(content: &str) -> Vec<Card> {
     content
         .split("\n")
         .filter(|line| !line.is_empty())
-        .map(|line: &str| -> u32 {
-            let num_winners = get_num_winners(line);
+        .map(parse_card)
+        .collect()
+}
 
-            if num_winners == 0 {
-                0
+trait ScoringRule {
+    fn score(&self, cards: &[Card]) -> Result<u32, String>;
+}
+
+// Part one: the first match is worth one point, every further match doubles it.
+struct DoublingPoints;
+
+impl ScoringRule for DoublingPoints {
+    fn score(&self, cards: &[Card]) -> Result<u32, String> {
+        cards.iter().try_fold(0_u32, |sum, card| {
+            let num_matches = card.num_matches();
+            let points = if num_matches == 0 {
+                Some(0)
             } else {
-                (2 as u32).pow(num_winners - 1)
-            }
+                2_u32.checked_pow(num_matches - 1)
+            };
+            points
+                .and_then(|points| sum.checked_add(points))
+                .ok_or_else(|| "the score doesn't fit in a u32".to_string())
         })
-        .sum()
+    }
 }
 
-fn part_two(content: &str) -> u32 {
-    let num_winners: Vec<u32> = content
-        .split("\n")
-        .filter(|line| !line.is_empty())
-        .map(get_num_winners)
-        .collect();
+// Every match is worth the same number of points.
+struct LinearPoints {
+    points_per_match: u32,
+}
+
+impl ScoringRule for LinearPoints {
+    fn score(&self, cards: &[Card]) -> Result<u32, String> {
+        cards.iter().try_fold(0_u32, |sum, card| {
+            card.num_matches()
+                .checked_mul(self.points_per_match)
+                .and_then(|points| sum.checked_add(points))
+                .ok_or_else(|| "the score doesn't fit in a u32".to_string())
+        })
+    }
+}
+
+// Part two (with `offset == 1`): a card with n matches wins a copy of each of
+// the n cards starting `offset` cards below it. The score is the total card count.
+struct CopyCards {
+    offset: usize,
+    overflow: CascadeOverflow,
+}
+
+impl ScoringRule for CopyCards {
+    fn score(&self, cards: &[Card]) -> Result<u32, String> {
+        let num_matches: Vec<u32> = cards.iter().map(Card::num_matches).collect();
+        cascade(&num_matches, self.offset, self.overflow)?.total()
+    }
+}
 
+fn part_one(content: &str) -> Result<u32, String> {
+    DoublingPoints.score(&parse_cards(content))
+}
+
+// What to do when a card wins copies of cards past the end of the table.
+#[derive(Debug, Clone, Copy)]
+enum CascadeOverflow {
+    // Copies that would land past the last card are dropped.
+    Clamp,
+    // Winning past the last card is an error.
+    Error,
+}
+
+#[derive(Debug)]
+struct Cascade {
+    num_cards: Vec<u32>,
+    // `provenance[i]` lists `(source_card_index, copies)` for every earlier
+    // card that produced copies of card `i`. Originals are not included.
+    provenance: Vec<Vec<(usize, u32)>>,
+}
+
+impl Cascade {
+    fn total(&self) -> Result<u32, String> {
+        self.num_cards.iter().try_fold(0_u32, |sum, count| {
+            sum.checked_add(*count)
+                .ok_or_else(|| "the card count doesn't fit in a u32".to_string())
+        })
+    }
+
+    // One line per card, e.g. "card 4: 8 = 1 original + 1 from card 1 + ...".
+    fn breakdown(&self) -> String {
+        self.num_cards
+            .iter()
+            .zip(self.provenance.iter())
+            .enumerate()
+            .map(|(i, (count, sources))| -> String {
+                let mut line = format!("card {}: {count} = 1 original", i + 1);
+                for (source, copies) in sources {
+                    line += &format!(" + {copies} from card {}", source + 1);
+                }
+                line + "\n"
+            })
+            .collect()
+    }
+}
+
+fn cascade(
+    num_winners: &[u32],
+    offset: usize,
+    overflow: CascadeOverflow,
+) -> Result<Cascade, String> {
+    if offset == 0 {
+        return Err("a card can only win copies of cards below it".to_string());
+    }
     let mut num_cards: Vec<u32> = vec![1; num_winners.len()];
+    let mut provenance: Vec<Vec<(usize, u32)>> = vec![Vec::new(); num_winners.len()];
     for i in 0..num_cards.len() {
-        for j in 0..(num_winners[i] as usize) {
-            num_cards[i + j + 1] += num_cards[i];
+        if num_winners[i] == 0 {
+            continue;
+        }
+        let first = i + offset;
+        let last = first + num_winners[i] as usize - 1;
+        if last >= num_cards.len() {
+            if let CascadeOverflow::Error = overflow {
+                return Err(format!(
+                    "card {} wins copies up to card {} but there are only {} cards",
+                    i + 1,
+                    last + 1,
+                    num_cards.len()
+                ));
+            }
+        }
+        for j in first..=last.min(num_cards.len() - 1) {
+            num_cards[j] = num_cards[j].checked_add(num_cards[i]).ok_or_else(|| {
+                format!(
+                    "the number of copies of card {} doesn't fit in a u32",
+                    j + 1
+                )
+            })?;
+            provenance[j].push((i, num_cards[i]));
         }
     }
 
-    num_cards.iter().sum()
+    Ok(Cascade {
+        num_cards,
+        provenance,
+    })
+}
+
+// The rule the part two total is scored with, so that the breakdown explains
+// that same total.
+const PART_TWO_RULE: CopyCards = CopyCards {
+    offset: 1,
+    overflow: CascadeOverflow::Clamp,
+};
+
+fn part_two(content: &str) -> Result<u32, String> {
+    PART_TWO_RULE.score(&parse_cards(content))
 }
 
 fn main() {
     let file_path = env::args().nth(1).unwrap();
     let content = fs::read_to_string(file_path).unwrap();
 
-    let answer1 = part_one(&content);
-    println!("the answer for the first part is: {answer1}");
+    match part_one(&content) {
+        Ok(answer1) => println!("the answer for the first part is: {answer1}"),
+        Err(err) => println!("the first part has no answer: {err}"),
+    }
+
+    match part_two(&content) {
+        Ok(answer2) => println!("the answer for the second part is: {answer2}"),
+        Err(err) => println!("the second part has no answer: {err}"),
+    }
+
+    if env::args().nth(2).as_deref() == Some("breakdown") {
+        let num_matches: Vec<u32> = parse_cards(&content)
+            .iter()
+            .map(Card::num_matches)
+            .collect();
+        match cascade(&num_matches, PART_TWO_RULE.offset, PART_TWO_RULE.overflow) {
+            Ok(cascade) => print!("{}", cascade.breakdown()),
+            Err(err) => println!("cannot build the breakdown: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `num_cards` cards that each have `num_matches` matches.
+    fn cards_with_matches(num_cards: usize, num_matches: u32) -> Vec<Card> {
+        (0..num_cards)
+            .map(|_| Card {
+                winning_nums: (0..num_matches).collect(),
+                our_nums: (0..num_matches).collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn answers() {
+        let path = format!("{}/example.txt", env!("CARGO_MANIFEST_DIR"));
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(part_one(&content), Ok(13));
+        assert_eq!(part_two(&content), Ok(30));
+    }
 
-    let answer2 = part_two(&content);
-    println!("the answer for the second part is: {answer2}");
+    // Scores past `u32::MAX` are errors for every rule, never a panic or a
+    // wrapped total.
+    #[test]
+    fn overflow_is_an_error() {
+        assert!(DoublingPoints.score(&cards_with_matches(1, 33)).is_err());
+        assert_eq!(
+            DoublingPoints.score(&cards_with_matches(1, 32)),
+            Ok(1 << 31)
+        );
+        let linear = LinearPoints {
+            points_per_match: u32::MAX,
+        };
+        assert!(linear.score(&cards_with_matches(2, 1)).is_err());
+        assert!(PART_TWO_RULE.score(&cards_with_matches(40, 5)).is_err());
+        assert_eq!(PART_TWO_RULE.score(&cards_with_matches(6, 5)), Ok(63));
+    }
 }
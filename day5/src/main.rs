@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use std::{cmp::max, cmp::min, collections::HashMap, env, fs, ops::Range};
+use std::{cmp::max, cmp::min, env, fmt, fs, ops::Range};
 
 fn parse_space_separated_nums(line: &str) -> Vec<u64> {
     line.split(" ")
@@ -16,6 +16,7 @@ struct RangePair {
 
 #[derive(Debug)]
 struct NamedRanges {
+    source_name: String,
     dest_name: String,
     ranges: Vec<RangePair>,
 }
@@ -31,9 +32,114 @@ impl NamedRanges {
     }
 }
 
-fn parse_number_map_lines(lines: &Vec<&str>) -> HashMap<String, NamedRanges> {
+const SEED: &str = "seed";
+const LOCATION: &str = "location";
+
+// All the maps of an almanac. Any category may map to several others, as long
+// as there is exactly one route between the categories being translated.
+#[derive(Debug)]
+struct Almanac {
+    maps: Vec<NamedRanges>,
+}
+
+// Something that can be pushed through an almanac: a single number or a range.
+trait Translate {
+    type Output;
+
+    fn translate_with(self, map: &PiecewiseMap) -> Self::Output;
+}
+
+impl Translate for u64 {
+    type Output = u64;
+
+    fn translate_with(self, map: &PiecewiseMap) -> u64 {
+        map.map_number(self)
+    }
+}
+
+impl Translate for Range<u64> {
+    type Output = IntervalSet<u64>;
+
+    fn translate_with(self, map: &PiecewiseMap) -> IntervalSet<u64> {
+        map.map_range(&IntervalSet::from_range(self))
+    }
+}
+
+impl Translate for IntervalSet<u64> {
+    type Output = IntervalSet<u64>;
+
+    fn translate_with(self, map: &PiecewiseMap) -> IntervalSet<u64> {
+        map.map_range(&self)
+    }
+}
+
+impl Almanac {
+    // The maps leading from `from` to `to`, in order. Fails if there is no
+    // such route or if there is more than one.
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&NamedRanges>, String> {
+        fn find_paths<'a>(
+            almanac: &'a Almanac,
+            cur: &str,
+            to: &str,
+            stack: &mut Vec<&'a NamedRanges>,
+            found: &mut Vec<Vec<&'a NamedRanges>>,
+        ) {
+            // two routes are enough to know the query is ambiguous
+            if found.len() >= 2 {
+                return;
+            }
+            if cur == to {
+                found.push(stack.clone());
+                return;
+            }
+            for map in almanac.maps.iter().filter(|m| m.source_name == cur) {
+                let is_cycle = map.dest_name == stack.first().map_or(cur, |m| &m.source_name)
+                    || stack.iter().any(|m| m.dest_name == map.dest_name);
+                if is_cycle {
+                    continue;
+                }
+                stack.push(map);
+                find_paths(almanac, &map.dest_name, to, stack, found);
+                stack.pop();
+            }
+        }
+
+        fn describe(path: &[&NamedRanges], from: &str) -> String {
+            path.iter()
+                .fold(from.to_string(), |acc, m| acc + " -> " + &m.dest_name)
+        }
+
+        let mut found = Vec::<Vec<&NamedRanges>>::new();
+        find_paths(self, from, to, &mut vec![], &mut found);
+        match found.len() {
+            0 => Err(format!("no route from {from} to {to}")),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(format!(
+                "ambiguous route from {from} to {to}: {} or {}",
+                describe(&found[0], from),
+                describe(&found[1], from)
+            )),
+        }
+    }
+
+    // Composes the maps along the route from `from` to `to` into a single map.
+    fn compose(&self, from: &str, to: &str) -> Result<PiecewiseMap, String> {
+        Ok(self
+            .path(from, to)?
+            .iter()
+            .fold(PiecewiseMap::identity(), |composed, number_map| {
+                composed.then(&PiecewiseMap::from_named_ranges(number_map))
+            }))
+    }
+
+    fn translate<T: Translate>(&self, from: &str, to: &str, input: T) -> Result<T::Output, String> {
+        Ok(input.translate_with(&self.compose(from, to)?))
+    }
+}
+
+fn parse_number_map_lines(lines: &[&str]) -> Almanac {
     let mut i: usize = 1;
-    let mut ranges_map = HashMap::<String, NamedRanges>::new();
+    let mut maps = Vec::<NamedRanges>::new();
     while i < lines.len() {
         let chunks: Vec<&str> = lines[i].split(['-', ' ']).collect();
         let (source, dest) = (chunks[0], chunks[2]);
@@ -49,15 +155,17 @@ fn parse_number_map_lines(lines: &Vec<&str>) -> HashMap<String, NamedRanges> {
             });
             i += 1;
         }
-        ranges_map.insert(
-            source.to_string(),
-            NamedRanges {
-                dest_name: dest.to_string(),
-                ranges,
-            },
-        );
+        maps.push(NamedRanges {
+            source_name: source.to_string(),
+            dest_name: dest.to_string(),
+            ranges,
+        });
     }
-    ranges_map
+    Almanac { maps }
+}
+
+fn parse_seeds(lines: &[&str]) -> Vec<u64> {
+    parse_space_separated_nums(lines[0].split_once(":").unwrap().1)
 }
 
 fn part_one(content: &str) -> u64 {
@@ -66,96 +174,483 @@ fn part_one(content: &str) -> u64 {
         .filter(|line| !line.is_empty())
         .collect();
 
-    let seeds = parse_space_separated_nums(lines[0].split_once(":").unwrap().1);
-    let source_to_number_map = parse_number_map_lines(&lines);
+    let seeds = parse_seeds(&lines);
+    let seed_to_location = parse_number_map_lines(&lines)
+        .compose(SEED, LOCATION)
+        .unwrap();
     seeds
         .iter()
-        .map(|seed| {
-            let mut source = "seed";
-            let mut cur_num = *seed;
-            loop {
-                let number_map = source_to_number_map.get(source).unwrap();
-                cur_num = number_map.map_number(cur_num);
+        .map(|seed| seed.translate_with(&seed_to_location))
+        .min()
+        .unwrap()
+}
 
-                source = &number_map.dest_name;
+// A set of values stored as sorted, disjoint, non-adjacent half-open ranges.
+#[derive(Debug, Clone, PartialEq)]
+struct IntervalSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T: Copy + Ord> IntervalSet<T> {
+    fn new() -> IntervalSet<T> {
+        IntervalSet { ranges: vec![] }
+    }
 
-                if source == "location" {
-                    return cur_num;
+    // Sorts the ranges, drops empty ones and merges the ones that overlap or touch.
+    fn from_ranges(ranges: impl IntoIterator<Item = Range<T>>) -> IntervalSet<T> {
+        let mut sorted: Vec<Range<T>> = ranges.into_iter().filter(|r| r.start < r.end).collect();
+        sorted.sort_by_key(|r| r.start);
+
+        let mut coalesced = Vec::<Range<T>>::new();
+        for r in sorted {
+            match coalesced.last_mut() {
+                Some(last) if r.start <= last.end => last.end = max(last.end, r.end),
+                _ => coalesced.push(r),
+            }
+        }
+        IntervalSet { ranges: coalesced }
+    }
+
+    fn from_range(range: Range<T>) -> IntervalSet<T> {
+        IntervalSet::from_ranges(std::iter::once(range))
+    }
+
+    fn ranges(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    fn min(&self) -> Option<T> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    fn contains(&self, value: T) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= value);
+        i < self.ranges.len() && self.ranges[i].start <= value
+    }
+
+    fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        IntervalSet::from_ranges(self.ranges.iter().chain(other.ranges.iter()).cloned())
+    }
+
+    fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut ranges = Vec::<Range<T>>::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = max(a.start, b.start);
+            let end = min(a.end, b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+            // advance whichever range finishes first
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut ranges = Vec::<Range<T>>::new();
+        let mut j = 0;
+        for a in &self.ranges {
+            let mut cursor = a.start;
+            // skip the ranges of `other` that end before `a` starts
+            while j < other.ranges.len() && other.ranges[j].end <= cursor {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < a.end {
+                let b = &other.ranges[k];
+                if cursor < b.start {
+                    ranges.push(cursor..b.start);
                 }
+                cursor = max(cursor, b.end);
+                k += 1;
             }
-        })
-        .min()
-        .unwrap()
+            if cursor < a.end {
+                ranges.push(cursor..a.end);
+            }
+        }
+        IntervalSet { ranges }
+    }
 }
 
-fn range_intersection(a: &Range<u64>, b: &Range<u64>) -> Option<Range<u64>> {
-    if a.start <= b.end && a.end >= b.start {
-        Some(Range {
-            start: max(a.start, b.start),
-            end: min(a.end, b.end),
-        })
-    } else {
-        None
+impl IntervalSet<u64> {
+    // Moves every value by `offset`. The caller is responsible for not
+    // shifting values out of `u64`.
+    fn shift(&self, offset: i64) -> IntervalSet<u64> {
+        IntervalSet {
+            ranges: self
+                .ranges
+                .iter()
+                .map(|r| r.start.wrapping_add_signed(offset)..r.end.wrapping_add_signed(offset))
+                .collect(),
+        }
     }
 }
 
-fn range_difference(a: &Range<u64>, b: &Range<u64>) -> Vec<Range<u64>> {
-    if a.end < b.start || a.start > b.end {
-        // no intersection
-        return vec![a.clone()];
+impl<T: Copy + Ord> FromIterator<Range<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> IntervalSet<T> {
+        IntervalSet::from_ranges(iter)
     }
+}
 
-    let mut r = vec![];
-    if a.start < b.start {
-        // left intersection, if present
-        r.push(Range {
-            start: a.start,
-            end: b.start,
-        });
-    }
-    if b.end < a.end {
-        // right intersection, if present
-        r.push(Range {
-            start: b.end + 1,
-            end: a.end,
-        });
+// A contiguous part of a stage's input together with where it went.
+#[derive(Debug)]
+struct TracePiece {
+    input: Range<u64>,
+    // Index into `NamedRanges::ranges` of the pair that matched, if any.
+    range_pair: Option<usize>,
+    output: Range<u64>,
+}
+
+impl NamedRanges {
+    // Splits `range` by the `RangePair` (if any) that maps each part of it.
+    // The pieces are sorted by their input.
+    fn trace_range(&self, range: &IntervalSet<u64>) -> Vec<TracePiece> {
+        let mut unmapped = range.clone();
+        let mut pieces = Vec::<TracePiece>::new();
+        for (i, rp) in self.ranges.iter().enumerate() {
+            let source = IntervalSet::from_range(rp.source_range.clone());
+            let offset = rp.dest_range.start as i64 - rp.source_range.start as i64;
+            for r in unmapped.intersection(&source).ranges() {
+                pieces.push(TracePiece {
+                    input: r.clone(),
+                    range_pair: Some(i),
+                    output: r.start.wrapping_add_signed(offset)..r.end.wrapping_add_signed(offset),
+                });
+            }
+            unmapped = unmapped.difference(&source);
+        }
+        for r in unmapped.ranges() {
+            pieces.push(TracePiece {
+                input: r.clone(),
+                range_pair: None,
+                output: r.clone(),
+            });
+        }
+        pieces.sort_by_key(|piece| piece.input.start);
+        pieces
     }
 
-    r
+    fn map_range(&self, range: &IntervalSet<u64>) -> IntervalSet<u64> {
+        self.trace_range(range)
+            .into_iter()
+            .map(|piece| piece.output)
+            .collect()
+    }
 }
 
 impl NamedRanges {
-    fn map_range(&self, range: &Range<u64>) -> Vec<Range<u64>> {
-        // Subranges of `range` that were mapped to the new ids.
-        let mut mapped_intersections: Vec<Range<u64>> = vec![];
-        // Ranges with the new ids.
-        let mut new_ranges: Vec<Range<u64>> = vec![];
+    // All numbers that `map_number` sends to `num`. There can be more than one,
+    // since an unmapped number may collide with the image of a `RangePair`.
+    fn map_number_inverse(&self, num: u64) -> Vec<u64> {
+        let mut preimage: Vec<u64> = self
+            .ranges
+            .iter()
+            .filter(|rp| rp.dest_range.contains(&num))
+            .map(|rp| num - rp.dest_range.start + rp.source_range.start)
+            // an earlier `RangePair` may map the same source elsewhere
+            .filter(|x| self.map_number(*x) == num)
+            .collect();
+        if !self.ranges.iter().any(|rp| rp.source_range.contains(&num)) {
+            preimage.push(num);
+        }
+        preimage
+    }
+
+    // All numbers that `map_range` sends into `range`.
+    fn map_range_inverse(&self, range: &IntervalSet<u64>) -> IntervalSet<u64> {
+        let mut preimage = IntervalSet::new();
+        // the sources of earlier `RangePair`s, which take precedence
+        let mut covered = IntervalSet::new();
         for rp in &self.ranges {
-            let i = range_intersection(range, &rp.source_range);
-            if i.is_some() {
-                // map the intersection to the destination range
-                let i = i.unwrap();
-                let new_start = i.start - rp.source_range.start + rp.dest_range.start;
-                new_ranges.push(Range {
-                    start: new_start,
-                    end: new_start + (i.end - i.start),
-                });
-                mapped_intersections.push(i);
-            }
+            let source = IntervalSet::from_range(rp.source_range.clone());
+            let dest = IntervalSet::from_range(rp.dest_range.clone());
+            let offset = rp.source_range.start as i64 - rp.dest_range.start as i64;
+            let mapped = range.intersection(&dest).shift(offset).difference(&covered);
+            preimage = preimage.union(&mapped);
+            covered = covered.union(&source);
         }
 
-        let mut unmapped_subranges: Vec<Range<u64>> = vec![range.clone()];
-        for mi in &mapped_intersections {
-            let mut new_unmapped_subranges: Vec<Range<u64>> = vec![];
-            for us in &unmapped_subranges {
-                new_unmapped_subranges.append(&mut range_difference(us, mi));
+        // the parts of `range` not covered by any source range map to themselves
+        let sources: IntervalSet<u64> = self
+            .ranges
+            .iter()
+            .map(|rp| rp.source_range.clone())
+            .collect();
+        preimage.union(&range.difference(&sources))
+    }
+}
+
+// A piece of a `PiecewiseMap`: every number in `start..end` is shifted by `offset`.
+#[derive(Debug, Clone)]
+struct Piece {
+    start: u64,
+    end: u64,
+    offset: i64,
+}
+
+impl Piece {
+    fn image(&self) -> Range<u64> {
+        self.start.wrapping_add_signed(self.offset)..self.end.wrapping_add_signed(self.offset)
+    }
+}
+
+// A function on `0..u64::MAX` made of sorted, contiguous pieces, each of which
+// shifts its numbers by a constant. Numbers not covered by any `RangePair` of
+// the original maps end up in pieces with a zero offset.
+#[derive(Debug)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    fn identity() -> PiecewiseMap {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                start: 0,
+                end: u64::MAX,
+                offset: 0,
+            }],
+        }
+    }
+
+    // Overlapping source ranges are resolved like `NamedRanges::map_number`
+    // does, by the first `RangePair` that matches.
+    fn from_named_ranges(named_ranges: &NamedRanges) -> PiecewiseMap {
+        let pieces = named_ranges
+            .trace_range(&IntervalSet::from_range(0..u64::MAX))
+            .into_iter()
+            .map(|piece| Piece {
+                start: piece.input.start,
+                end: piece.input.end,
+                offset: piece.output.start.wrapping_sub(piece.input.start) as i64,
+            })
+            .collect();
+        PiecewiseMap { pieces }
+    }
+
+    // Index of the piece containing `num`.
+    fn piece_index(&self, num: u64) -> usize {
+        self.pieces.partition_point(|piece| piece.end <= num)
+    }
+
+    // `u64::MAX` is past the last piece, but no `RangePair` can contain it
+    // either, so it maps to itself.
+    fn map_number(&self, num: u64) -> u64 {
+        let offset = self
+            .pieces
+            .get(self.piece_index(num))
+            .map_or(0, |p| p.offset);
+        num.wrapping_add_signed(offset)
+    }
+
+    fn map_range(&self, range: &IntervalSet<u64>) -> IntervalSet<u64> {
+        range
+            .ranges()
+            .iter()
+            .flat_map(|r| {
+                self.pieces[self.piece_index(r.start)..]
+                    .iter()
+                    .take_while(|piece| piece.start < r.end)
+                    .map(|piece| {
+                        let start = max(piece.start, r.start);
+                        let end = min(piece.end, r.end);
+                        start.wrapping_add_signed(piece.offset)
+                            ..end.wrapping_add_signed(piece.offset)
+                    })
+            })
+            .collect()
+    }
+
+    // Returns the map equivalent to applying `self` and then `next`.
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut pieces = Vec::<Piece>::new();
+        for piece in &self.pieces {
+            let image = piece.image();
+            for next_piece in next.pieces[next.piece_index(image.start)..]
+                .iter()
+                .take_while(|next_piece| next_piece.start < image.end)
+            {
+                let start = max(image.start, next_piece.start);
+                let end = min(image.end, next_piece.end);
+                let offset = piece.offset + next_piece.offset;
+                let start = start.wrapping_add_signed(-piece.offset);
+                let end = end.wrapping_add_signed(-piece.offset);
+                match pieces.last_mut() {
+                    // merge neighbours with the same shift
+                    Some(last) if last.end == start && last.offset == offset => last.end = end,
+                    _ => pieces.push(Piece { start, end, offset }),
+                }
             }
-            unmapped_subranges = new_unmapped_subranges;
         }
-        new_ranges.append(&mut unmapped_subranges);
+        PiecewiseMap { pieces }
+    }
+}
+
+impl fmt::Display for PiecewiseMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for piece in &self.pieces {
+            let image = piece.image();
+            writeln!(
+                f,
+                "[{}, {}) -> [{}, {}) ({:+})",
+                piece.start, piece.end, image.start, image.end, piece.offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// All seeds that end up at `location`.
+fn seeds_for_location(almanac: &Almanac, location: u64) -> Vec<u64> {
+    let mut cur_nums = vec![location];
+    for number_map in almanac.path(SEED, LOCATION).unwrap().iter().rev() {
+        cur_nums = cur_nums
+            .iter()
+            .flat_map(|num| number_map.map_number_inverse(*num))
+            .collect();
+    }
+    cur_nums
+}
+
+// Walks `range` from `LOCATION` back to `SEED`.
+fn map_range_inverse(almanac: &Almanac, range: &IntervalSet<u64>) -> IntervalSet<u64> {
+    almanac
+        .path(SEED, LOCATION)
+        .unwrap()
+        .iter()
+        .rev()
+        .fold(range.clone(), |cur, number_map| {
+            number_map.map_range_inverse(&cur)
+        })
+}
+
+// The parts of `seed_ranges` that reach a location below `threshold`.
+fn seed_ranges_below(
+    almanac: &Almanac,
+    seed_ranges: &IntervalSet<u64>,
+    threshold: u64,
+) -> IntervalSet<u64> {
+    map_range_inverse(almanac, &IntervalSet::from_range(0..threshold)).intersection(seed_ranges)
+}
+
+#[derive(Debug)]
+struct TraceStep<'a> {
+    number_map: &'a NamedRanges,
+    pieces: Vec<TracePiece>,
+}
+
+impl Almanac {
+    // Follows `input` from `from` to `to`, recording how every stage split and
+    // moved it.
+    fn trace(
+        &self,
+        from: &str,
+        to: &str,
+        input: &IntervalSet<u64>,
+    ) -> Result<Vec<TraceStep<'_>>, String> {
+        let mut cur = input.clone();
+        let mut steps = Vec::<TraceStep>::new();
+        for number_map in self.path(from, to)? {
+            let pieces = number_map.trace_range(&cur);
+            cur = pieces.iter().map(|piece| piece.output.clone()).collect();
+            steps.push(TraceStep { number_map, pieces });
+        }
+        Ok(steps)
+    }
+}
+
+// Formats a range as a single number when it holds just one.
+fn describe_range(r: &Range<u64>) -> String {
+    if r.end - r.start == 1 {
+        r.start.to_string()
+    } else {
+        format!("{}..{}", r.start, r.end)
+    }
+}
 
-        new_ranges
+fn trace_to_text(steps: &[TraceStep]) -> String {
+    let mut out = String::new();
+    for step in steps {
+        out += &format!(
+            "{} -> {}:\n",
+            step.number_map.source_name, step.number_map.dest_name
+        );
+        for piece in &step.pieces {
+            let rule = match piece.range_pair {
+                Some(i) => {
+                    let rp = &step.number_map.ranges[i];
+                    format!(
+                        "via `{} {} {}`",
+                        rp.dest_range.start,
+                        rp.source_range.start,
+                        rp.source_range.end - rp.source_range.start
+                    )
+                }
+                None => "unmapped".to_string(),
+            };
+            out += &format!(
+                "  {} -> {} ({rule})\n",
+                describe_range(&piece.input),
+                describe_range(&piece.output)
+            );
+        }
     }
+    out
+}
+
+fn trace_to_json(steps: &[TraceStep]) -> String {
+    let range_json = |r: &Range<u64>| format!("{{\"start\":{},\"end\":{}}}", r.start, r.end);
+    let steps_json: Vec<String> = steps
+        .iter()
+        .map(|step| {
+            let pieces_json: Vec<String> = step
+                .pieces
+                .iter()
+                .map(|piece| {
+                    let range_pair = match piece.range_pair {
+                        Some(i) => {
+                            let rp = &step.number_map.ranges[i];
+                            format!(
+                                "{{\"index\":{i},\"source\":{},\"dest\":{}}}",
+                                range_json(&rp.source_range),
+                                range_json(&rp.dest_range)
+                            )
+                        }
+                        None => "null".to_string(),
+                    };
+                    format!(
+                        "{{\"input\":{},\"range_pair\":{range_pair},\"output\":{}}}",
+                        range_json(&piece.input),
+                        range_json(&piece.output)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"from\":\"{}\",\"to\":\"{}\",\"pieces\":[{}]}}",
+                step.number_map.source_name,
+                step.number_map.dest_name,
+                pieces_json.join(",")
+            )
+        })
+        .collect();
+    format!("{{\"steps\":[{}]}}\n", steps_json.join(","))
+}
+
+fn parse_seed_ranges(lines: &[&str]) -> IntervalSet<u64> {
+    parse_seeds(lines)
+        .chunks(2)
+        .map(|w| w[0]..w[0] + w[1])
+        .collect()
 }
 
 fn part_two(content: &str) -> u64 {
@@ -164,38 +659,46 @@ fn part_two(content: &str) -> u64 {
         .filter(|line| !line.is_empty())
         .collect();
 
-    let seed_ranges: Vec<Range<u64>> =
-        parse_space_separated_nums(lines[0].split_once(":").unwrap().1)
-            .chunks(2)
-            .map(|w| w[0]..w[0] + w[1])
-            .collect();
-    let source_to_number_map = parse_number_map_lines(&lines);
+    let seed_ranges = parse_seed_ranges(&lines);
+    let seed_to_location = parse_number_map_lines(&lines)
+        .compose(SEED, LOCATION)
+        .unwrap();
 
-    seed_ranges
-        .iter()
-        .map(|seed_range| {
-            let mut cur_ranges: Vec<Range<u64>> = vec![seed_range.clone(); 1];
-            let mut cur_source = "seed";
+    seed_ranges.translate_with(&seed_to_location).min().unwrap()
+}
 
-            loop {
-                let number_map = source_to_number_map.get(cur_source).unwrap();
-                let mut next_ranges: Vec<Range<u64>> = vec![];
+// Same answer as `part_two`, found by scanning the locations in increasing
+// order and checking whether any of them comes from a seed range.
+fn part_two_inverse(content: &str) -> u64 {
+    let lines: Vec<&str> = content
+        .split("\n")
+        .filter(|line| !line.is_empty())
+        .collect();
 
-                cur_ranges.iter().for_each(|r| {
-                    let mut new_ranges = number_map.map_range(r);
-                    next_ranges.append(&mut new_ranges);
-                });
+    let seed_ranges = parse_seed_ranges(&lines);
+    let almanac = parse_number_map_lines(&lines);
 
-                cur_source = &number_map.dest_name;
-                cur_ranges = next_ranges;
+    // Rather than going one location at a time, whole blocks of locations that
+    // no seed reaches are skipped at once; a block that is reached is split in
+    // halves, lower half first.
+    fn lowest_reached(
+        almanac: &Almanac,
+        seed_ranges: &IntervalSet<u64>,
+        locations: Range<u64>,
+    ) -> Option<u64> {
+        let preimage = map_range_inverse(almanac, &IntervalSet::from_range(locations.clone()));
+        if preimage.intersection(seed_ranges).is_empty() {
+            return None;
+        }
+        if locations.end - locations.start == 1 {
+            return Some(locations.start);
+        }
+        let mid = locations.start + (locations.end - locations.start) / 2;
+        lowest_reached(almanac, seed_ranges, locations.start..mid)
+            .or_else(|| lowest_reached(almanac, seed_ranges, mid..locations.end))
+    }
 
-                if cur_source == "location" {
-                    return cur_ranges.iter().map(|r| r.start).min().unwrap();
-                }
-            }
-        })
-        .min()
-        .unwrap()
+    lowest_reached(&almanac, &seed_ranges, 0..u64::MAX).unwrap()
 }
 
 fn main() {
@@ -207,4 +710,235 @@ fn main() {
 
     let answer2 = part_two(&content);
     println!("the answer for the second part is: {answer2}");
+
+    let lines: Vec<&str> = content
+        .split("\n")
+        .filter(|line| !line.is_empty())
+        .collect();
+    let almanac = parse_number_map_lines(&lines);
+    match env::args().nth(2).as_deref() {
+        Some("inverse") => println!(
+            "scanning the locations upwards gives: {}",
+            part_two_inverse(&content)
+        ),
+        Some("composed") => print!("{}", almanac.compose(SEED, LOCATION).unwrap()),
+        Some("seeds-for") => {
+            let location: u64 = env::args().nth(3).unwrap().parse().unwrap();
+            let seeds = seeds_for_location(&almanac, location);
+            println!("location {location} is reached from seeds {seeds:?}");
+        }
+        Some("below") => {
+            let threshold: u64 = env::args().nth(3).unwrap().parse().unwrap();
+            let seed_ranges = seed_ranges_below(&almanac, &parse_seed_ranges(&lines), threshold);
+            println!(
+                "seed ranges reaching locations below {threshold}: {:?}",
+                seed_ranges.ranges()
+            );
+        }
+        Some(mode @ ("trace" | "trace-json")) => {
+            // `trace <seed> [<length>]` follows a single seed or a seed range.
+            let start: u64 = env::args().nth(3).unwrap().parse().unwrap();
+            let length: u64 = env::args().nth(4).map_or(1, |l| l.parse().unwrap());
+            let steps = almanac
+                .trace(
+                    SEED,
+                    LOCATION,
+                    &IntervalSet::from_range(start..start + length),
+                )
+                .unwrap();
+            if mode == "trace" {
+                print!("{}", trace_to_text(&steps));
+            } else {
+                print!("{}", trace_to_json(&steps));
+            }
+        }
+        Some("translate") => {
+            let args: Vec<String> = env::args().skip(3).collect();
+            let (from, to) = (&args[0], &args[1]);
+            let value: u64 = args[2].parse().unwrap();
+            match almanac.translate(from, to, value) {
+                Ok(translated) => println!("{from} {value} is {to} {translated}"),
+                Err(err) => println!("cannot translate: {err}"),
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_input(name: &str) -> String {
+        fs::read_to_string(format!("{}/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    #[test]
+    fn part_two_inverse_matches_part_two() {
+        for name in ["example.txt", "real.txt"] {
+            let content = read_input(name);
+            assert_eq!(part_two_inverse(&content), part_two(&content), "{name}");
+        }
+    }
+
+    // A tiny LCG, so the random inputs are the same on every run.
+    struct Lcg {
+        state: u64,
+    }
+
+    impl Lcg {
+        fn new() -> Lcg {
+            Lcg {
+                state: 0x2545f4914f6cdd1d,
+            }
+        }
+
+        // A pseudo-random number in `0..bound`.
+        fn next(&mut self, bound: u64) -> u64 {
+            self.state = self
+                .state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.state >> 33) % bound
+        }
+
+        // A set of up to four ranges somewhere in `0..max_value`.
+        fn interval_set(&mut self, max_value: u64) -> IntervalSet<u64> {
+            (0..self.next(5))
+                .map(|_| {
+                    let start = self.next(max_value - 10);
+                    start..start + self.next(10)
+                })
+                .collect()
+        }
+    }
+
+    fn elements(set: &IntervalSet<u64>) -> std::collections::BTreeSet<u64> {
+        set.ranges().iter().flat_map(|r| r.clone()).collect()
+    }
+
+    // The `IntervalSet` operations against a plain per-element set on random
+    // small inputs.
+    #[test]
+    fn interval_set_matches_element_sets() {
+        let mut rng = Lcg::new();
+        let is_canonical = |set: &IntervalSet<u64>| -> bool {
+            set.ranges().iter().all(|r| r.start < r.end)
+                && set.ranges().windows(2).all(|w| w[0].end < w[1].start)
+        };
+
+        for _ in 0..10000 {
+            let a = rng.interval_set(50);
+            let b = rng.interval_set(50);
+            let (ea, eb) = (elements(&a), elements(&b));
+
+            let union = a.union(&b);
+            let intersection = a.intersection(&b);
+            let difference = a.difference(&b);
+            let offset = rng.next(20) as i64 - 10;
+            let shifted = a.shift(offset);
+            for set in [&a, &b, &union, &intersection, &difference] {
+                assert!(is_canonical(set), "not coalesced: {set:?}");
+            }
+            assert_eq!(elements(&union), &ea | &eb, "{a:?} | {b:?}");
+            assert_eq!(elements(&intersection), &ea & &eb, "{a:?} & {b:?}");
+            assert_eq!(elements(&difference), &ea - &eb, "{a:?} - {b:?}");
+            // only shift sets that stay within `u64`
+            if offset >= 0 || a.min().is_none_or(|m| m >= offset.unsigned_abs()) {
+                let expected: std::collections::BTreeSet<u64> =
+                    ea.iter().map(|x| x.wrapping_add_signed(offset)).collect();
+                assert!(is_canonical(&shifted), "not coalesced: {shifted:?}");
+                assert_eq!(elements(&shifted), expected, "{a:?} >> {offset}");
+            }
+            for x in 0..60 {
+                assert_eq!(a.contains(x), ea.contains(&x), "{x} in {a:?}");
+            }
+        }
+    }
+
+    // Every map of `almanac`, and the composed seed-to-location map, against
+    // `map_number` applied to each element of random small ranges.
+    fn check_almanac(almanac: &Almanac, num_rounds: usize) {
+        let mut rng = Lcg::new();
+        let max_value = 1 + almanac
+            .maps
+            .iter()
+            .flat_map(|m| m.ranges.iter())
+            .map(|rp| max(rp.source_range.end, rp.dest_range.end))
+            .max()
+            .unwrap_or(0);
+        let max_value = max(max_value, 20);
+        let composed = almanac.compose(SEED, LOCATION).unwrap();
+
+        for _ in 0..num_rounds {
+            let input = rng.interval_set(max_value);
+            for number_map in &almanac.maps {
+                let expected: std::collections::BTreeSet<u64> = elements(&input)
+                    .iter()
+                    .map(|x| number_map.map_number(*x))
+                    .collect();
+                assert_eq!(elements(&number_map.map_range(&input)), expected);
+
+                // `x` is in the preimage of `input` exactly when it is mapped into it
+                let preimage = number_map.map_range_inverse(&input);
+                for x in elements(&preimage) {
+                    assert!(input.contains(number_map.map_number(x)));
+                }
+                for y in elements(&input) {
+                    for x in number_map.map_number_inverse(y) {
+                        assert!(preimage.contains(x));
+                    }
+                }
+                for _ in 0..100 {
+                    let x = rng.next(max_value);
+                    assert_eq!(
+                        preimage.contains(x),
+                        input.contains(number_map.map_number(x))
+                    );
+                }
+            }
+
+            let expected: std::collections::BTreeSet<u64> = elements(&input)
+                .iter()
+                .map(|x| {
+                    almanac
+                        .path(SEED, LOCATION)
+                        .unwrap()
+                        .iter()
+                        .fold(*x, |x, m| m.map_number(x))
+                })
+                .collect();
+            assert_eq!(elements(&composed.map_range(&input)), expected);
+        }
+    }
+
+    // Overlapping source ranges go to the first `RangePair` that matches, and
+    // `u64::MAX` maps to itself.
+    #[test]
+    fn overlaps_and_the_largest_number() {
+        let content = "seeds: 7 12 18446744073709551615\n\nseed-to-soil map:\n50 0 10\n70 5 10\n\nsoil-to-location map:\n0 60 1\n";
+        let lines: Vec<&str> = content
+            .split("\n")
+            .filter(|line| !line.is_empty())
+            .collect();
+        let almanac = parse_number_map_lines(&lines);
+        check_almanac(&almanac, 200);
+        let composed = almanac.compose(SEED, LOCATION).unwrap();
+        assert_eq!(composed.map_number(7), 57);
+        assert_eq!(composed.map_number(12), 77);
+        assert_eq!(composed.map_number(u64::MAX), u64::MAX);
+        assert_eq!(part_one(content), 57);
+    }
+
+    #[test]
+    fn almanac_maps_match_map_number() {
+        for name in ["example.txt", "real.txt"] {
+            let content = read_input(name);
+            let lines: Vec<&str> = content
+                .split("\n")
+                .filter(|line| !line.is_empty())
+                .collect();
+            check_almanac(&parse_number_map_lines(&lines), 200);
+        }
+    }
 }
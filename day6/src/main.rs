@@ -8,20 +8,149 @@ fn parse_space_separated_nums(line: &str) -> Vec<u64> {
         .collect()
 }
 
+// Counts the hold times `h` in `0..=time` with `h * (time - h) > distance`,
+// using integer arithmetic only.
 fn num_ways_to_beat(time: u64, distance: u64) -> u64 {
-    let discriminant = (time * time - 4 * distance) as f64;
-    assert!(discriminant > 0.0);
-    let first_root = (((time as f64) - discriminant.sqrt()) / 2.0).floor() as u64 + 1;
-    let second_root = ((time as f64) + discriminant.sqrt()) / 2.0;
-    let second_root = if second_root.fract() == 0.0 {
-        second_root.floor() as u64 - 1
-    } else {
-        second_root.floor() as u64
-    };
-    second_root - first_root + 1
+    let (time, distance) = (time as u128, distance as u128);
+    let beats = |hold: u128| hold * (time - hold) > distance;
+
+    // the travelled distance peaks at half the race time
+    if !beats(time / 2) {
+        return 0;
+    }
+    // Since the peak wins, `time^2 > 4 * distance`. The smallest winning hold is
+    // right next to the smaller root `(time - sqrt(time^2 - 4 * distance)) / 2`;
+    // the integer square root can put us off by one, so nudge it into place.
+    let discriminant = time * time - 4 * distance;
+    let mut first = (time - discriminant.isqrt()) / 2;
+    while !beats(first) {
+        first += 1;
+    }
+    while first > 0 && beats(first - 1) {
+        first -= 1;
+    }
+    // the curve is symmetric, so the last winning hold is `time - first`
+    (time - 2 * first + 1) as u64
+}
+
+// How far a boat gets in a race. `distance` must be unimodal in `hold` over
+// `0..=time`: strictly increasing up to its peak and never increasing after it.
+trait BoatModel {
+    fn distance(&self, time: u64, hold: u64) -> u128;
+
+    // The hold time with the largest distance.
+    fn peak(&self, time: u64) -> u64 {
+        // first hold time after which the distance stops growing
+        let (mut lo, mut hi) = (0, time);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.distance(time, mid) < self.distance(time, mid + 1) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    fn num_ways_to_beat(&self, time: u64, distance: u64) -> u64 {
+        count_by_binary_search(self, time, distance)
+    }
+}
+
+// Counts the winning hold times by binary searching for the first winning
+// hold before the peak and the last one after it.
+fn count_by_binary_search<M: BoatModel + ?Sized>(model: &M, time: u64, distance: u64) -> u64 {
+    let beats = |hold: u64| model.distance(time, hold) > distance as u128;
+    let peak = model.peak(time);
+    if !beats(peak) {
+        return 0;
+    }
+
+    // first winning hold in `0..=peak`
+    let (mut lo, mut hi) = (0, peak);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if beats(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    let first = lo;
+
+    // last winning hold in `peak..=time`
+    let (mut lo, mut hi) = (peak, time);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if beats(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    let last = lo;
+
+    last - first + 1
 }
 
-fn part_one(content: &str) -> u64 {
+// The puzzle's boat: every millisecond of holding adds one unit of speed.
+struct UnitCharge;
+
+impl BoatModel for UnitCharge {
+    fn distance(&self, time: u64, hold: u64) -> u128 {
+        hold as u128 * (time - hold) as u128
+    }
+
+    fn peak(&self, time: u64) -> u64 {
+        time / 2
+    }
+
+    fn num_ways_to_beat(&self, time: u64, distance: u64) -> u64 {
+        num_ways_to_beat(time, distance)
+    }
+}
+
+// Every millisecond of holding adds `rate` units of speed.
+struct ChargeRate {
+    rate: u64,
+}
+
+impl BoatModel for ChargeRate {
+    fn distance(&self, time: u64, hold: u64) -> u128 {
+        hold as u128 * self.rate as u128 * (time - hold) as u128
+    }
+}
+
+// Like `ChargeRate`, but the speed can't go over `max_speed`.
+struct MaxSpeed {
+    rate: u64,
+    max_speed: u64,
+}
+
+impl BoatModel for MaxSpeed {
+    fn distance(&self, time: u64, hold: u64) -> u128 {
+        let speed = (hold as u128 * self.rate as u128).min(self.max_speed as u128);
+        speed * (time - hold) as u128
+    }
+}
+
+// Like `ChargeRate`, but every millisecond of holding also costs
+// `cost_per_hold` extra milliseconds before the boat can start moving.
+struct ChargeCost {
+    rate: u64,
+    cost_per_hold: u64,
+}
+
+impl BoatModel for ChargeCost {
+    fn distance(&self, time: u64, hold: u64) -> u128 {
+        let lost = hold as u128 * (1 + self.cost_per_hold as u128);
+        let moving = (time as u128).saturating_sub(lost);
+        hold as u128 * self.rate as u128 * moving
+    }
+}
+
+fn part_one(content: &str, model: &dyn BoatModel) -> u64 {
     let (time_str, distance_str) = content.split_once("\n").unwrap();
     let times = parse_space_separated_nums(time_str.split_once(":").unwrap().1);
     let distances = parse_space_separated_nums(
@@ -34,12 +163,12 @@ fn part_one(content: &str) -> u64 {
     );
 
     zip(times, distances)
-        .map(|(time, distance)| num_ways_to_beat(time, distance))
+        .map(|(time, distance)| model.num_ways_to_beat(time, distance))
         .reduce(|a, b| a * b)
         .unwrap()
 }
 
-fn part_two(content: &str) -> u64 {
+fn part_two(content: &str, model: &dyn BoatModel) -> u64 {
     let (time_str, distance_str) = content.split_once("\n").unwrap();
     let time: u64 = time_str
         .chars()
@@ -54,17 +183,87 @@ fn part_two(content: &str) -> u64 {
         .parse()
         .unwrap();
 
-    num_ways_to_beat(time, distance)
-
+    model.num_ways_to_beat(time, distance)
 }
 
 fn main() {
     let file_path = env::args().nth(1).unwrap();
     let content = fs::read_to_string(file_path).unwrap();
 
-    let answer1 = part_one(&content);
+    let answer1 = part_one(&content, &UnitCharge);
     println!("the answer for the first part is: {answer1}");
 
-    let answer2 = part_two(&content);
+    let answer2 = part_two(&content, &UnitCharge);
     println!("the answer for the second part is: {answer2}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Races are drawn from a fixed-seed LCG so failures can be replayed.
+    struct Lcg {
+        state: u64,
+    }
+
+    impl Lcg {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.state = self
+                .state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.state >> 33) % bound
+        }
+    }
+
+    fn num_ways_to_beat_linear(model: &dyn BoatModel, time: u64, distance: u64) -> u64 {
+        (0..=time)
+            .filter(|hold| model.distance(time, *hold) > distance as u128)
+            .count() as u64
+    }
+
+    #[test]
+    fn models_match_linear_scan() {
+        let mut rng = Lcg { state: 6 };
+        for _ in 0..100000 {
+            let time = rng.next(200);
+            let models: Vec<Box<dyn BoatModel>> = vec![
+                Box::new(UnitCharge),
+                Box::new(ChargeRate {
+                    rate: 1 + rng.next(5),
+                }),
+                Box::new(MaxSpeed {
+                    rate: 1 + rng.next(5),
+                    max_speed: 1 + rng.next(100),
+                }),
+                Box::new(ChargeCost {
+                    rate: 1 + rng.next(5),
+                    cost_per_hold: rng.next(3),
+                }),
+            ];
+            for model in models {
+                let peak = model.distance(time, model.peak(time));
+                let distance = rng.next(peak as u64 + 10);
+                let expected = num_ways_to_beat_linear(model.as_ref(), time, distance);
+                assert_eq!(
+                    model.num_ways_to_beat(time, distance),
+                    expected,
+                    "time {time}, distance {distance}"
+                );
+                assert_eq!(
+                    count_by_binary_search(model.as_ref(), time, distance),
+                    expected,
+                    "time {time}, distance {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn answers() {
+        let content =
+            fs::read_to_string(format!("{}/example.txt", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert_eq!(part_one(&content, &UnitCharge), 288);
+        assert_eq!(part_two(&content, &UnitCharge), 71503);
+    }
+}
@@ -1,110 +1,283 @@
 #![allow(dead_code)]
-use std::{
-    cmp::{Ordering, Reverse},
-    collections::HashMap,
-    env, fs,
-};
+use std::{cmp::Reverse, collections::HashMap, env, fs};
 
+// Ranks of the cards in a hand, as indices into `RuleSet::card_order`.
 type Hand = [u64; 5];
 type HandAndBid = (Hand, u64);
 
-fn parse_hand(hand_str: &str, use_jokers: bool) -> Hand {
-    let chars_str = if use_jokers {
-        "J23456789TQKA"
-    } else {
-        "23456789TJQKA"
-    };
+// Named hand categories, from the weakest to the strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
+
+// How wildcards may be used when determining the `HandType`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WildcardMode {
+    // A wildcard stands in for whichever card makes the hand strongest.
+    Full,
+    // A wildcard can only turn an unmatched card into a pair; leftover
+    // wildcards only match each other.
+    PairsOnly,
+}
+
+#[derive(Debug, Clone)]
+struct RuleSet {
+    // All the cards, from the weakest to the strongest.
+    card_order: Vec<char>,
+    wildcards: Vec<char>,
+    wildcard_mode: WildcardMode,
+}
+
+impl RuleSet {
+    fn standard() -> RuleSet {
+        RuleSet {
+            card_order: "23456789TJQKA".chars().collect(),
+            wildcards: vec![],
+            wildcard_mode: WildcardMode::Full,
+        }
+    }
+
+    fn jokers() -> RuleSet {
+        RuleSet {
+            card_order: "J23456789TQKA".chars().collect(),
+            wildcards: vec!['J'],
+            wildcard_mode: WildcardMode::Full,
+        }
+    }
+
+    // Parses `key = value` lines, e.g.
+    //   card_order = J23456789TQKA
+    //   wildcards = J
+    //   wildcard_mode = pairs-only
+    // Keys that are left out keep their values from `RuleSet::standard`.
+    fn from_config(config: &str) -> Result<RuleSet, String> {
+        let mut rules = RuleSet::standard();
+        for line in config.split("\n") {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once("=")
+                .ok_or_else(|| format!("line should contain =: {line}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "card_order" => rules.card_order = value.chars().collect(),
+                "wildcards" => rules.wildcards = value.chars().collect(),
+                "wildcard_mode" => {
+                    rules.wildcard_mode = match value {
+                        "full" => WildcardMode::Full,
+                        "pairs-only" => WildcardMode::PairsOnly,
+                        _ => return Err(format!("unknown wildcard mode: {value}")),
+                    }
+                }
+                key => return Err(format!("unknown rule: {key}")),
+            }
+        }
+        if let Some(c) = rules
+            .wildcards
+            .iter()
+            .find(|c| !rules.card_order.contains(c))
+        {
+            return Err(format!("wildcard {c} is not ranked"));
+        }
+        Ok(rules)
+    }
+
+    fn load(path: &str) -> Result<RuleSet, String> {
+        let config = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        RuleSet::from_config(&config)
+    }
+
+    fn card(&self, rank: u64) -> char {
+        self.card_order[rank as usize]
+    }
+
+    fn is_wildcard(&self, rank: u64) -> bool {
+        self.wildcards.contains(&self.card(rank))
+    }
+}
+
+fn parse_hand(hand_str: &str, rules: &RuleSet) -> Hand {
     hand_str
         .chars()
-        .map(|c| chars_str.chars().position(|x| x == c).unwrap() as u64)
+        .map(|c| rules.card_order.iter().position(|x| *x == c).unwrap() as u64)
         .collect::<Vec<u64>>()
         .try_into()
         .unwrap()
 }
 
-fn hand_strength(hand: &Hand, use_jokers: bool) -> u64 {
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct HandStrength {
+    hand_type: HandType,
+    // The rank each wildcard of the hand was resolved to, in hand order. A
+    // wildcard that didn't help resolves to itself.
+    wildcards_as: Vec<u64>,
+}
+
+fn hand_strength(hand: &Hand, rules: &RuleSet) -> HandStrength {
     let mut card_type_counts: HashMap<u64, u64> = HashMap::new();
-    let mut num_jokers = 0;
+    let mut wildcards = Vec::<u64>::new();
     hand.iter().for_each(|x| {
-        if use_jokers && *x == 0 {
-            // If this is a joker, then don't count it for `card_type_counts`.
-            num_jokers += 1;
+        if rules.is_wildcard(*x) {
+            // If this is a wildcard, then don't count it for `card_type_counts`.
+            wildcards.push(*x);
             return;
         }
         *card_type_counts.entry(*x).or_insert(0) += 1;
     });
 
-    if num_jokers == 5 {
-        // Corner case - treat 5 jokers as five of a kind.
-        return 6;
-    }
-    let mut card_type_counts: Vec<(u64, u64)> =
-        Vec::from_iter(card_type_counts.iter().map(|(a, b)| (*b, *a)));
-    card_type_counts.sort_by_key(|x| Reverse(*x));
-
-    let highest_num_of_same_kind = card_type_counts[0].0 + num_jokers;
-    match highest_num_of_same_kind {
-        // five of a kind
-        5 => 6,
-        // four of a kind
-        4 => 5,
-        3 => {
-            match card_type_counts[1].0 {
-                // full house
-                2 => 4,
-                // three of a kind
-                _ => 3,
+    // Groups of equal cards as `(size, rank)`, the biggest (and then the
+    // strongest) first.
+    let mut groups: Vec<(u64, u64)> = card_type_counts
+        .iter()
+        .map(|(rank, size)| (*size, *rank))
+        .collect();
+    groups.sort_by_key(|x| Reverse(*x));
+    let mut wildcards_as = wildcards.clone();
+    match rules.wildcard_mode {
+        WildcardMode::Full => match groups.first_mut() {
+            Some(biggest) => {
+                biggest.0 += wildcards.len() as u64;
+                wildcards_as.fill(biggest.1);
             }
-        }
-        2 => {
-            match card_type_counts[1].0 {
-                // two pairs
-                2 => 2,
-                // one pair
-                _ => 1,
+            // Corner case - a hand of wildcards only is five of a kind.
+            None => groups.push((wildcards.len() as u64, wildcards[0])),
+        },
+        WildcardMode::PairsOnly => {
+            let mut unused = 0;
+            for group in groups.iter_mut().filter(|group| group.0 == 1) {
+                if unused == wildcards.len() {
+                    break;
+                }
+                group.0 = 2;
+                wildcards_as[unused] = group.1;
+                unused += 1;
             }
+            if unused < wildcards.len() {
+                groups.push(((wildcards.len() - unused) as u64, wildcards[unused]));
+            }
+            groups.sort_by_key(|x| Reverse(*x));
         }
-        // highest card
-        _ => 0,
     }
+
+    let hand_type = match (groups[0].0, groups.get(1).map(|group| group.0)) {
+        (5, _) => HandType::FiveOfAKind,
+        (4, _) => HandType::FourOfAKind,
+        (3, Some(2)) => HandType::FullHouse,
+        (3, _) => HandType::ThreeOfAKind,
+        (2, Some(2)) => HandType::TwoPair,
+        (2, _) => HandType::OnePair,
+        _ => HandType::HighCard,
+    };
+    HandStrength {
+        hand_type,
+        wildcards_as,
+    }
+}
+
+#[derive(Debug)]
+struct RankedHand {
+    hand: Hand,
+    bid: u64,
+    hand_type: HandType,
+    wildcards_as: Vec<u64>,
 }
 
-fn solve(content: &str, use_jokers: bool) -> u64 {
-    let mut hands_and_bids: Vec<HandAndBid> = content
+// All the hands, from the weakest to the strongest.
+fn rank_hands(content: &str, rules: &RuleSet) -> Vec<RankedHand> {
+    let mut hands_and_bids: Vec<(HandType, HandAndBid, Vec<u64>)> = content
         .split("\n")
         .filter(|line| !line.is_empty())
         .map(|line| {
             let (hand_str, bid_str) = line.split_once(" ").unwrap();
-            let hand = parse_hand(hand_str, use_jokers);
+            let hand = parse_hand(hand_str, rules);
             let bid: u64 = bid_str.parse().unwrap();
 
-            (hand, bid)
+            let strength = hand_strength(&hand, rules);
+            (strength.hand_type, (hand, bid), strength.wildcards_as)
         })
         .collect();
 
-    hands_and_bids.sort_by(|a: &HandAndBid, b: &HandAndBid| -> Ordering {
-        let strength_a = hand_strength(&a.0, use_jokers);
-        let strength_b = hand_strength(&b.0, use_jokers);
-        if strength_a != strength_b {
-            return strength_a.cmp(&strength_b);
-        }
-
-        return a.cmp(b);
-    });
+    // Stronger hand types win, ties are broken card by card.
+    hands_and_bids.sort_by_key(|a| (a.0, a.1));
 
     hands_and_bids
+        .into_iter()
+        .map(|(hand_type, (hand, bid), wildcards_as)| RankedHand {
+            hand,
+            bid,
+            hand_type,
+            wildcards_as,
+        })
+        .collect()
+}
+
+fn solve(content: &str, rules: &RuleSet) -> u64 {
+    rank_hands(content, rules)
         .iter()
         .enumerate()
-        .map(|(index, hb)| hb.1 * (index as u64 + 1))
+        .map(|(index, ranked)| ranked.bid * (index as u64 + 1))
         .sum()
 }
 
+// One line per hand in final rank order, with its type, what its wildcards
+// became, what put it above the previous hand and what it adds to the total.
+fn explain(content: &str, rules: &RuleSet) -> String {
+    let ranked = rank_hands(content, rules);
+    let hand_str = |hand: &Hand| -> String { hand.iter().map(|rank| rules.card(*rank)).collect() };
+
+    let mut out = String::new();
+    for (index, cur) in ranked.iter().enumerate() {
+        let wildcards: String = if cur.wildcards_as.is_empty() {
+            "-".to_string()
+        } else {
+            cur.wildcards_as
+                .iter()
+                .map(|rank| rules.card(*rank))
+                .collect()
+        };
+        let decided_by = match index.checked_sub(1).map(|i| &ranked[i]) {
+            None => "weakest hand".to_string(),
+            Some(prev) if prev.hand_type != cur.hand_type => {
+                format!("type beats {} ({:?})", hand_str(&prev.hand), prev.hand_type)
+            }
+            Some(prev) => match (0..5).find(|i| prev.hand[*i] != cur.hand[*i]) {
+                Some(i) => format!(
+                    "card {}: {} beats {} of {}",
+                    i + 1,
+                    rules.card(cur.hand[i]),
+                    rules.card(prev.hand[i]),
+                    hand_str(&prev.hand)
+                ),
+                None => format!("ties with {}", hand_str(&prev.hand)),
+            },
+        };
+        let rank = index as u64 + 1;
+        out += &format!(
+            "{rank:>5}: {} {:<14} wildcards: {wildcards:<5} {decided_by:<40} {} * {rank} = {}\n",
+            hand_str(&cur.hand),
+            format!("{:?}", cur.hand_type),
+            cur.bid,
+            cur.bid * rank,
+        );
+    }
+    out
+}
+
 fn part_one(content: &str) -> u64 {
-    solve(content, false)
+    solve(content, &RuleSet::standard())
 }
 
 fn part_two(content: &str) -> u64 {
-    solve(content, true)
+    solve(content, &RuleSet::jokers())
 }
 
 fn main() {
@@ -116,4 +289,57 @@ fn main() {
 
     let answer2 = part_two(&content);
     println!("the answer for the second part is: {answer2}");
+
+    // `explain [<rules file>]` prints the ranking report for the part two
+    // rules or for the given ones, `rules <rules file>` solves with the given
+    // rules.
+    match env::args().nth(2).as_deref() {
+        Some("explain") => {
+            let rules = match env::args().nth(3) {
+                Some(rules_path) => RuleSet::load(&rules_path),
+                None => Ok(RuleSet::jokers()),
+            };
+            match rules {
+                Ok(rules) => print!("{}", explain(&content, &rules)),
+                Err(err) => println!("can't load the rules: {err}"),
+            }
+        }
+        Some("rules") => match RuleSet::load(&env::args().nth(3).unwrap()) {
+            Ok(rules) => {
+                let answer = solve(&content, &rules);
+                println!("the answer with the custom rules is: {answer}");
+            }
+            Err(err) => println!("can't load the rules: {err}"),
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_errors() {
+        assert!(RuleSet::from_config("card_order: 23456789TJQKA\n").is_err());
+        assert!(RuleSet::from_config("wildcard_mode = some\n").is_err());
+        assert!(RuleSet::from_config("jokers = J\n").is_err());
+        assert!(RuleSet::from_config("wildcards = X\n").is_err());
+    }
+
+    #[test]
+    fn non_ascii_cards() {
+        let rules = RuleSet::from_config("card_order = ★23456789TQKA\nwildcards = ★\n").unwrap();
+        let hand = parse_hand("★★234", &rules);
+        assert_eq!(hand[0], 0);
+        assert_eq!(rules.card(hand[0]), '★');
+        assert!(rules.is_wildcard(hand[0]));
+        assert_eq!(
+            hand_strength(&hand, &rules).hand_type,
+            HandType::ThreeOfAKind
+        );
+        let path = format!("{}/example.txt", env!("CARGO_MANIFEST_DIR"));
+        let content = fs::read_to_string(path).unwrap().replace('J', "★");
+        assert_eq!(solve(&content, &rules), 5905);
+    }
 }
@@ -1,69 +1,348 @@
 #![allow(dead_code)]
-use num::integer::lcm;
+use num::integer::{ExtendedGcd, Integer};
 use std::{collections::HashMap, env, fs};
 
-fn parse_steps_and_graph(content: &str) -> (String, HashMap<String, (String, String)>) {
+// The L/R instructions packed into bits, set bits meaning "go right".
+#[derive(Debug)]
+struct Steps {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl Steps {
+    fn parse(line: &str) -> Steps {
+        let mut bits = vec![0; line.len().div_ceil(64)];
+        for (i, c) in line.bytes().enumerate() {
+            if c == b'R' {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        Steps {
+            bits,
+            len: line.len(),
+        }
+    }
+
+    fn goes_right(&self, step_index: usize) -> bool {
+        self.bits[step_index / 64] & (1 << (step_index % 64)) != 0
+    }
+}
+
+impl std::fmt::Display for Steps {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for i in 0..self.len {
+            write!(f, "{}", if self.goes_right(i) { 'R' } else { 'L' })?;
+        }
+        Ok(())
+    }
+}
+
+// The network with node names interned into dense ids, `0..names.len()`.
+#[derive(Debug)]
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    // `(left, right)` children of every node, indexed by id.
+    children: Vec<(u32, u32)>,
+    ends_with_z: Vec<bool>,
+}
+
+impl Network {
+    fn id(&self, name: &str) -> u32 {
+        self.ids[name]
+    }
+
+    fn next(&self, node: u32, steps: &Steps, step_index: usize) -> u32 {
+        let (left, right) = self.children[node as usize];
+        if steps.goes_right(step_index) {
+            right
+        } else {
+            left
+        }
+    }
+
+    // All node ids, sorted by name.
+    fn sorted_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = (0..self.names.len() as u32).collect();
+        ids.sort_by_key(|id| &self.names[*id as usize]);
+        ids
+    }
+}
+
+fn parse_steps_and_graph(content: &str) -> (Steps, Network) {
     let lines: Vec<&str> = content.split("\n").filter(|l| !l.is_empty()).collect();
-    let steps = lines[0].to_string();
-    let mut graph: HashMap<String, (String, String)> = HashMap::new();
+    let steps = Steps::parse(lines[0]);
+
+    let mut network = Network {
+        names: vec![],
+        ids: HashMap::new(),
+        children: vec![],
+        ends_with_z: vec![],
+    };
+    let mut intern = |name: &str| -> u32 {
+        if let Some(id) = network.ids.get(name) {
+            return *id;
+        }
+        let id = network.names.len() as u32;
+        network.names.push(name.to_string());
+        network.ids.insert(name.to_string(), id);
+        network.children.push((u32::MAX, u32::MAX));
+        network.ends_with_z.push(name.ends_with("Z"));
+        id
+    };
+    let mut edges = Vec::<(u32, u32, u32)>::new();
     lines[1..].iter().for_each(|line| {
         let (cur_node, children) = line.split_once(" = (").unwrap();
         let (left_child, right_child) = children.split_once(", ").unwrap();
         let right_child = right_child.replace(")", "");
-        graph.insert(cur_node.to_string(), (left_child.to_string(), right_child));
+        edges.push((intern(cur_node), intern(left_child), intern(&right_child)));
     });
-    (steps, graph)
+    for (node, left, right) in edges {
+        network.children[node as usize] = (left, right);
+    }
+    (steps, network)
 }
 
 fn part_one(content: &str) -> u64 {
-    let (steps, graph) = parse_steps_and_graph(content);
+    let (steps, network) = parse_steps_and_graph(content);
     let mut num_steps: u64 = 0;
-    let mut cur_node = "AAA";
+    let mut cur_node = network.id("AAA");
+    let end = network.id("ZZZ");
     loop {
-        let cur_action = steps.as_bytes()[(num_steps as usize) % steps.len()];
-        let children = graph.get(cur_node).unwrap();
-        if cur_action == b'L' {
-            cur_node = &children.0;
-        } else {
-            cur_node = &children.1;
-        }
+        cur_node = network.next(cur_node, &steps, (num_steps as usize) % steps.len);
         num_steps += 1;
-        if cur_node == "ZZZ" {
+        if cur_node == end {
             break;
         }
     }
     num_steps
 }
 
-fn part_two(content: &str) -> u64 {
-    let (steps, graph) = parse_steps_and_graph(content);
-    let cur_nodes: Vec<&str> = graph
-        .keys()
-        .filter(|k| k.ends_with("A"))
-        .map(|k| k.as_str())
-        .collect();
-    let num_steps: Vec<u64> = cur_nodes
+// Where a ghost starting at some node sits on Z-ending nodes. Walking the
+// network is deterministic in `(node, step index mod steps.len())`, so after
+// `tail` steps the ghost loops with period `period`.
+#[derive(Debug)]
+struct GhostCycle {
+    tail: u64,
+    period: u64,
+    // Times (in steps) before the loop starts at which the ghost is on a Z node.
+    tail_hits: Vec<u64>,
+    // Times in `tail..tail + period` at which the ghost is on a Z node; each one
+    // repeats every `period` steps.
+    cycle_hits: Vec<u64>,
+}
+
+impl GhostCycle {
+    fn is_hit(&self, time: u64) -> bool {
+        if time < self.tail {
+            self.tail_hits.contains(&time)
+        } else {
+            let in_cycle = self.tail + (time - self.tail) % self.period;
+            self.cycle_hits.contains(&in_cycle)
+        }
+    }
+}
+
+fn find_cycle(steps: &Steps, network: &Network, start: u32) -> GhostCycle {
+    // `first_seen[node * steps.len + step_index]` is the first time the ghost
+    // was in that state, `u64::MAX` if it never was
+    let mut first_seen: Vec<u64> = vec![u64::MAX; network.names.len() * steps.len];
+    let mut hits = Vec::<u64>::new();
+    let mut cur_node = start;
+    let mut num_steps: u64 = 0;
+    let mut step_index: usize = 0;
+    loop {
+        let state = cur_node as usize * steps.len + step_index;
+        if first_seen[state] != u64::MAX {
+            let tail = first_seen[state];
+            let (tail_hits, cycle_hits) = hits.iter().partition(|hit| **hit < tail);
+            return GhostCycle {
+                tail,
+                period: num_steps - tail,
+                tail_hits,
+                cycle_hits,
+            };
+        }
+        first_seen[state] = num_steps;
+        // The ghost has to take at least one step, so the start doesn't count.
+        if num_steps > 0 && network.ends_with_z[cur_node as usize] {
+            hits.push(num_steps);
+        }
+
+        cur_node = network.next(cur_node, steps, step_index);
+        num_steps += 1;
+        step_index += 1;
+        if step_index == steps.len {
+            step_index = 0;
+        }
+    }
+}
+
+// Merges `x = a1 (mod n1)` and `x = a2 (mod n2)` into `x = a (mod lcm(n1, n2))`,
+// if there is any solution. The moduli don't need to be coprime.
+fn combine_congruences((a1, n1): (i128, i128), (a2, n2): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = n1.extended_gcd(&n2);
+    if (a2 - a1) % gcd != 0 {
+        return None;
+    }
+    let lcm = n1 / gcd * n2;
+    // `x * n1 = gcd (mod n2)`, so stepping `k * n1` with this `k` fixes the residue
+    let k = ((a2 - a1) / gcd % (n2 / gcd)) * x % (n2 / gcd);
+    Some(((a1 + k * n1).rem_euclid(lcm), lcm))
+}
+
+// The first time (at least one step in) at which every ghost is on a Z node.
+fn first_common_hit(cycles: &[GhostCycle]) -> Result<u64, String> {
+    let Some(max_tail) = cycles.iter().map(|c| c.tail).max() else {
+        return Err("there are no ghosts, no node ends with A".to_string());
+    };
+
+    // Before `max_tail` some ghosts are not looping yet, so check the times at
+    // which the first ghost is on a Z node one by one.
+    let first = &cycles[0];
+    let mut early_hits: Vec<u64> = first.tail_hits.clone();
+    for hit in &first.cycle_hits {
+        early_hits.extend((*hit..max_tail).step_by(first.period as usize));
+    }
+    early_hits.sort();
+    if let Some(time) = early_hits
+        .into_iter()
+        .find(|time| cycles.iter().all(|c| c.is_hit(*time)))
+    {
+        return Ok(time);
+    }
+
+    // From `max_tail` on every ghost loops, so every combination of in-cycle
+    // hits is a system of congruences.
+    let mut systems: Vec<(i128, i128)> = vec![(0, 1)];
+    for cycle in cycles {
+        systems = systems
+            .iter()
+            .flat_map(|system| {
+                cycle.cycle_hits.iter().filter_map(|hit| {
+                    combine_congruences(*system, (*hit as i128, cycle.period as i128))
+                })
+            })
+            .collect();
+    }
+    let time = systems
         .iter()
-        .map(|x| {
-            let mut num_steps: u64 = 0;
-            let mut cur_node = *x;
-            loop {
-                let cur_action = steps.as_bytes()[(num_steps as usize) % steps.len()];
-                let children = graph.get(cur_node).unwrap();
-                if cur_action == b'L' {
-                    cur_node = &children.0;
-                } else {
-                    cur_node = &children.1;
-                }
-                num_steps += 1;
-                if cur_node.ends_with("Z") {
-                    break;
-                }
+        .map(|(a, n)| {
+            // the smallest `a + k * n` that is at least `max_tail`
+            let missing = (max_tail as i128 - a).max(0);
+            a + (missing + n - 1) / n * n
+        })
+        .min()
+        .ok_or_else(|| "the ghosts are never on Z nodes at the same time".to_string())?;
+    u64::try_from(time)
+        .map_err(|_| format!("the ghosts first meet after {time} steps, past what a u64 holds"))
+}
+
+fn start_nodes(network: &Network) -> Vec<u32> {
+    network
+        .sorted_ids()
+        .into_iter()
+        .filter(|id| network.names[*id as usize].ends_with("A"))
+        .collect()
+}
+
+// The node a ghost starting at `start` is on after `time` steps.
+fn node_at(steps: &Steps, network: &Network, start: u32, time: u64) -> u32 {
+    let mut cur_node = start;
+    for num_steps in 0..time {
+        cur_node = network.next(cur_node, steps, (num_steps as usize) % steps.len);
+    }
+    cur_node
+}
+
+impl GhostCycle {
+    // Taking the `lcm` of the first Z times is only right when the ghost is on a
+    // Z node exactly at the multiples of its period.
+    fn lcm_shortcut_is_valid(&self) -> bool {
+        self.tail_hits.is_empty() && self.cycle_hits == [self.period]
+    }
+}
+
+fn cycle_report(content: &str) -> String {
+    let (steps, network) = parse_steps_and_graph(content);
+    let name = |id: u32| &network.names[id as usize];
+    let mut out = String::new();
+    for start in start_nodes(&network) {
+        let cycle = find_cycle(&steps, &network, start);
+        out += &format!(
+            "{}: tail {}, period {}, lcm shortcut {}\n",
+            name(start),
+            cycle.tail,
+            cycle.period,
+            if cycle.lcm_shortcut_is_valid() {
+                "valid"
+            } else {
+                "invalid"
             }
-            num_steps
+        );
+        for hit in &cycle.tail_hits {
+            let node = name(node_at(&steps, &network, start, *hit));
+            out += &format!("  {node} at step {hit} (before the loop)\n");
+        }
+        for hit in &cycle.cycle_hits {
+            let node = name(node_at(&steps, &network, start, *hit));
+            out += &format!(
+                "  {node} at step {hit}, position {} in the loop\n",
+                hit - cycle.tail
+            );
+        }
+    }
+    out
+}
+
+// The network in Graphviz format; start nodes are green and Z nodes are red.
+fn to_dot(content: &str) -> String {
+    let (_, network) = parse_steps_and_graph(content);
+    let name = |id: u32| &network.names[id as usize];
+    let mut out = "digraph network {\n".to_string();
+    for id in network.sorted_ids() {
+        let node = name(id);
+        let (left, right) = network.children[id as usize];
+        if node.ends_with("A") {
+            out += &format!("  \"{node}\" [color=green];\n");
+        } else if node.ends_with("Z") {
+            out += &format!("  \"{node}\" [color=red];\n");
+        }
+        out += &format!("  \"{node}\" -> \"{}\" [label=\"L\"];\n", name(left));
+        out += &format!("  \"{node}\" -> \"{}\" [label=\"R\"];\n", name(right));
+    }
+    out + "}\n"
+}
+
+fn to_json(content: &str) -> String {
+    let (steps, network) = parse_steps_and_graph(content);
+    let name = |id: u32| &network.names[id as usize];
+    let nodes: Vec<String> = network
+        .sorted_ids()
+        .into_iter()
+        .map(|id| {
+            let (left, right) = network.children[id as usize];
+            format!(
+                "\"{}\":{{\"L\":\"{}\",\"R\":\"{}\"}}",
+                name(id),
+                name(left),
+                name(right)
+            )
         })
         .collect();
-    num_steps.into_iter().reduce(|a, b| lcm(a, b)).unwrap()
+    format!(
+        "{{\"steps\":\"{steps}\",\"nodes\":{{{}}}}}\n",
+        nodes.join(",")
+    )
+}
+
+fn part_two(content: &str) -> Result<u64, String> {
+    let (steps, network) = parse_steps_and_graph(content);
+    let cycles: Vec<GhostCycle> = start_nodes(&network)
+        .into_iter()
+        .map(|start| find_cycle(&steps, &network, start))
+        .collect();
+    first_common_hit(&cycles)
 }
 
 fn main() {
@@ -73,6 +352,25 @@ fn main() {
     let answer1 = part_one(&content);
     println!("the answer for the first part is: {answer1}");
 
-    let answer2 = part_two(&content);
-    println!("the answer for the second part is: {answer2}");
+    match part_two(&content) {
+        Ok(answer2) => println!("the answer for the second part is: {answer2}"),
+        Err(err) => println!("the second part has no answer: {err}"),
+    }
+
+    match env::args().nth(2).as_deref() {
+        Some("report") => print!("{}", cycle_report(&content)),
+        Some("dot") => print!("{}", to_dot(&content)),
+        Some("json") => print!("{}", to_json(&content)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_ghosts_is_an_error() {
+        assert!(part_two("LR\n\nBBB = (BBB, BBB)\n").is_err());
+    }
 }
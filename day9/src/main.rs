@@ -8,60 +8,245 @@ fn parse_space_separated_nums(line: &str) -> Vec<i64> {
         .collect()
 }
 
-fn part_one(content: &str) -> i64 {
-    fn extrapolate_forwards(line: &str) -> i64 {
-        let nums = parse_space_separated_nums(line);
-        let mut difs: Vec<Vec<i64>> = vec![nums];
-        loop {
-            let cur_difs: Vec<i64> = difs
-                .last()
-                .unwrap()
+// A history as the polynomial through its values at `x = 0, 1, 2, ...`, stored
+// in Newton form: `first_difs[j]` is the j-th forward difference at `x = 0`.
+#[derive(Debug)]
+struct Polynomial {
+    first_difs: Vec<i128>,
+}
+
+impl Polynomial {
+    // `None` if the differences never become a (non-empty) row of zeros, that is
+    // if no polynomial of degree below `nums.len() - 1` goes through `nums`.
+    // Every row of differences can double the values, so they can overflow
+    // `i128` even though `nums` are `i64`.
+    fn fit(nums: &[i64]) -> Result<Option<Polynomial>, String> {
+        let mut first_difs = Vec::<i128>::new();
+        let mut difs: Vec<i128> = nums.iter().map(|x| *x as i128).collect();
+        while !difs.iter().all(|x| *x == 0) {
+            first_difs.push(difs[0]);
+            difs = difs
                 .windows(2)
-                .map(|w| w[1] - w[0])
-                .collect();
-            if cur_difs.iter().all(|x| *x == 0) {
-                break;
-            }
-            difs.push(cur_difs);
+                .map(|w| w[1].checked_sub(w[0]))
+                .collect::<Option<_>>()
+                .ok_or_else(|| "i128 overflow".to_string())?;
         }
+        if difs.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Polynomial { first_difs }))
+    }
 
-        difs.iter().rev().map(|x| x.last().unwrap()).sum()
+    fn degree(&self) -> usize {
+        self.first_difs.len().saturating_sub(1)
     }
 
-    let lines: Vec<&str> = content.split("\n").filter(|l| !l.is_empty()).collect();
-    lines.iter().map(|line| extrapolate_forwards(line)).sum()
+    // Newton's forward formula: `p(x) = sum over j of C(x, j) * first_difs[j]`,
+    // which holds for negative `x` as well. `None` if `i128` overflows.
+    fn value_at(&self, x: i128) -> Option<i128> {
+        let mut value: i128 = 0;
+        // `C(x, j)`, starting with `C(x, 0) = 1`
+        let mut binomial: i128 = 1;
+        for (j, dif) in self.first_difs.iter().enumerate() {
+            if j > 0 {
+                // `C(x, j) = C(x, j - 1) * (x - j + 1) / j`, and the division is exact
+                binomial = binomial.checked_mul(x - j as i128 + 1)? / j as i128;
+            }
+            value = value.checked_add(binomial.checked_mul(*dif)?)?;
+        }
+        Some(value)
+    }
 }
 
-fn part_two(content: &str) -> i64 {
-    fn extrapolate_backwards(line: &str) -> i64 {
-        let nums = parse_space_separated_nums(line);
-        let mut difs: Vec<Vec<i64>> = vec![nums];
-        loop {
-            let cur_difs: Vec<i64> = difs
-                .last()
-                .unwrap()
-                .windows(2)
-                .map(|w| w[0] - w[1])
-                .collect();
-            if cur_difs.iter().all(|x| *x == 0) {
-                break;
+#[derive(Debug)]
+enum History {
+    Polynomial(Polynomial),
+    // No polynomial of low enough degree could be confirmed, but one might be
+    // with `num_needed` values.
+    InsufficientData {
+        num_values: usize,
+        num_needed: usize,
+    },
+    // Not a polynomial of degree at most `max_degree`.
+    NonPolynomial {
+        max_degree: usize,
+    },
+    // The differences overflowed before reaching a row of zeros.
+    Overflow,
+}
+
+// Without `max_degree` any history that isn't confirmed to be a polynomial
+// could still be one of a higher degree, so it is never `NonPolynomial`.
+fn classify(nums: &[i64], max_degree: Option<usize>) -> History {
+    match Polynomial::fit(nums) {
+        Ok(Some(polynomial)) => return History::Polynomial(polynomial),
+        Ok(None) => {}
+        Err(_) => return History::Overflow,
+    }
+    // Confirming degree `d` takes `d + 2` values: the differences have to
+    // reach a row of zeros with at least one element.
+    match max_degree {
+        Some(max_degree) if nums.len() >= max_degree + 2 => History::NonPolynomial { max_degree },
+        Some(max_degree) => History::InsufficientData {
+            num_values: nums.len(),
+            num_needed: max_degree + 2,
+        },
+        None => History::InsufficientData {
+            num_values: nums.len(),
+            num_needed: nums.len() + 1,
+        },
+    }
+}
+
+impl std::fmt::Display for History {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            History::Polynomial(polynomial) => {
+                write!(f, "polynomial of degree {}", polynomial.degree())
+            }
+            History::InsufficientData {
+                num_values,
+                num_needed,
+            } => write!(
+                f,
+                "insufficient data: {num_values} values, at least {num_needed} needed"
+            ),
+            History::NonPolynomial { max_degree } => {
+                write!(f, "not a polynomial of degree at most {max_degree}")
             }
-            difs.push(cur_difs);
+            History::Overflow => write!(f, "the differences don't fit in an i128"),
         }
+    }
+}
 
-        difs.iter().rev().map(|x| x.first().unwrap()).sum()
+fn fit_or_refuse(nums: &[i64]) -> Result<Polynomial, String> {
+    match classify(nums, None) {
+        History::Polynomial(polynomial) => Ok(polynomial),
+        history => Err(format!("can't extrapolate: {history}")),
     }
+}
+
+// The value `k` steps after the last one in `nums`.
+fn predict_forwards(nums: &[i64], k: u64) -> Result<i128, String> {
+    fit_or_refuse(nums)?
+        .value_at(nums.len() as i128 - 1 + k as i128)
+        .ok_or_else(|| "i128 overflow".to_string())
+}
+
+// The value `k` steps before the first one in `nums`.
+fn predict_backwards(nums: &[i64], k: u64) -> Result<i128, String> {
+    fit_or_refuse(nums)?
+        .value_at(-(k as i128))
+        .ok_or_else(|| "i128 overflow".to_string())
+}
+
+fn part_one(content: &str) -> Result<i128, String> {
+    let lines: Vec<&str> = content.split("\n").filter(|l| !l.is_empty()).collect();
+    lines.iter().try_fold(0_i128, |sum, line| {
+        let prediction = predict_forwards(&parse_space_separated_nums(line), 1)?;
+        sum.checked_add(prediction)
+            .ok_or_else(|| "i128 overflow".to_string())
+    })
+}
+
+fn part_two(content: &str) -> Result<i128, String> {
     let lines: Vec<&str> = content.split("\n").filter(|l| !l.is_empty()).collect();
-    lines.iter().map(|line| extrapolate_backwards(line)).sum()
+    lines.iter().try_fold(0_i128, |sum, line| {
+        let prediction = predict_backwards(&parse_space_separated_nums(line), 1)?;
+        sum.checked_add(prediction)
+            .ok_or_else(|| "i128 overflow".to_string())
+    })
 }
 
 fn main() {
     let file_path = env::args().nth(1).unwrap();
     let content = fs::read_to_string(file_path).unwrap();
 
-    let answer1 = part_one(&content);
-    println!("the answer for the first part is: {answer1}");
+    match part_one(&content) {
+        Ok(answer1) => println!("the answer for the first part is: {answer1}"),
+        Err(err) => println!("the first part has no answer: {err}"),
+    }
+
+    match part_two(&content) {
+        Ok(answer2) => println!("the answer for the second part is: {answer2}"),
+        Err(err) => println!("the second part has no answer: {err}"),
+    }
 
-    let answer2 = part_two(&content);
-    println!("the answer for the second part is: {answer2}");
+    match env::args().nth(2).as_deref() {
+        // `predict <k>` shows every history's degree and its values `k` steps away.
+        Some("predict") => {
+            let k: u64 = env::args().nth(3).unwrap().parse().unwrap();
+            for line in content.split("\n").filter(|l| !l.is_empty()) {
+                let nums = parse_space_separated_nums(line);
+                let show = |value: Result<i128, String>| match value {
+                    Ok(value) => value.to_string(),
+                    Err(err) => err,
+                };
+                println!(
+                    "{}: {} steps back {}, {k} steps ahead {}",
+                    classify(&nums, None),
+                    k,
+                    show(predict_backwards(&nums, k)),
+                    show(predict_forwards(&nums, k))
+                );
+            }
+        }
+        // `classify [<max degree>]` says what kind of sequence every history is.
+        Some("classify") => {
+            let max_degree: Option<usize> = env::args().nth(3).map(|d| d.parse().unwrap());
+            for line in content.split("\n").filter(|l| !l.is_empty()) {
+                println!(
+                    "{line}: {}",
+                    classify(&parse_space_separated_nums(line), max_degree)
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every row of differences of `10^18, -10^18, ...` doubles the values.
+    fn alternating(len: usize) -> Vec<i64> {
+        (0..len)
+            .map(|i| {
+                if i % 2 == 0 {
+                    1_000_000_000_000_000_000
+                } else {
+                    -1_000_000_000_000_000_000
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn answers() {
+        let path = format!("{}/example.txt", env!("CARGO_MANIFEST_DIR"));
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(part_one(&content), Ok(114));
+        assert_eq!(part_two(&content), Ok(2));
+    }
+
+    #[test]
+    fn differences_past_i128_are_an_error() {
+        assert!(Polynomial::fit(&alternating(70)).is_err());
+        assert!(predict_forwards(&alternating(70), 1).is_err());
+    }
+
+    #[test]
+    fn classify_reports_overflow() {
+        for max_degree in [None, Some(3), Some(100)] {
+            assert!(matches!(
+                classify(&alternating(70), max_degree),
+                History::Overflow
+            ));
+        }
+        assert!(matches!(
+            classify(&alternating(60), Some(3)),
+            History::NonPolynomial { max_degree: 3 }
+        ));
+    }
 }